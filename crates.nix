@@ -19,12 +19,15 @@
           runtimeLibs = with pkgs; [
             wayland
             vulkan-loader
+            pipewire
           ];
           depsDrvConfig = {
             mkDerivation = {
               nativeBuildInputs = with pkgs; [
                 pkg-config
                 libxkbcommon
+                pipewire
+                rustPlatform.bindgenHook
               ];
             };
           };
@@ -0,0 +1,468 @@
+//! Derive macros of rbar
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned, ToTokens};
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, GenericArgument, Ident,
+    PathArguments, Type,
+};
+
+/// Registers the modules of the bar.
+///
+/// Goes on the enum holding the running modules, with one `Name(ModuleInfo<Module>)` variant
+/// per module, and generates everything the app needs to drive them:
+/// - `ModuleConfig`, with the config of every module and its mouse bindings,
+///   as written in `config.ron`
+/// - `ModuleEvent`, with the widget events of every module, as sent over IPC
+/// - `ModuleWidgetMsg`, with the widget events coming from the UI of a single module
+/// - `ModuleActionMsg`, with the bound mouse input on a single module
+/// - `ModuleRunnerMsg`, with what the running modules report back to the app
+/// - `view`, `update`, `tooltip`, `popup`, `has_popup`, `close_popup`, `state`, `subscription` and friends
+///   on the enum itself,
+///   plus the `ModuleGetSet` lookups and the event routing on `ModuleGroups`
+///
+/// ```ignore
+/// #[derive(From, Modules)]
+/// pub enum AnyModule {
+///     Clock(ModuleInfo<Clock>),
+///     Battery(ModuleInfo<Battery>),
+/// }
+/// ```
+#[proc_macro_derive(Modules)]
+pub fn derive_modules(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    modules(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct ModuleVariant {
+    name: Ident,
+    ty: Type,
+}
+
+fn modules(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let variants = variants(&input)?;
+
+    let DeriveInput { vis, ident, .. } = &input;
+    let names = variants.iter().map(|v| &v.name).collect::<Vec<_>>();
+    let tys = variants.iter().map(|v| &v.ty).collect::<Vec<_>>();
+    let name_strs = names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+
+    // Points "not a module" errors at the variant that registers the type
+    let assertions = tys.iter().map(|ty| {
+        quote_spanned! {ty.span()=>
+            assert_module::<#ty>();
+        }
+    });
+
+    // The generated items assume the bounds the assertions check instead of checking them
+    // again, which would repeat the error for every item. Being higher-ranked, they aren't
+    // rejected as trivially false
+    let bounds = quote! {
+        where #(for<'__m> #tys: crate::module::Module,)*
+    };
+
+    Ok(quote! {
+        const _: () = {
+            fn assert_module<M: crate::module::Module>() {}
+
+            fn assert_modules() {
+                #(#assertions)*
+            }
+        };
+
+        impl #ident #bounds {
+            pub fn view(
+                &self,
+            ) -> ::iced::Element<'_, crate::app::AppMsg, ::iced::Theme, ::iced::Renderer> {
+                match self {
+                    #(Self::#names(m) => {
+                        let config = m.config();
+                        let view = m.view().map(move |e| {
+                            crate::app::AppMsg::ModuleWidget(ModuleWidgetMsg::#names(
+                                config.clone(),
+                                e,
+                            ))
+                        });
+
+                        crate::module::actions::bindings(view, m.actions(), move |trigger| {
+                            crate::app::AppMsg::ModuleAction(ModuleActionMsg::#names(
+                                config.clone(),
+                                trigger,
+                            ))
+                        })
+                    })*
+                }
+            }
+
+            pub fn update(&mut self) -> Option<crate::app::AppMsg> {
+                match self {
+                    #(Self::#names(m) => m.update(),)*
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(Self::#names(_) => #name_strs,)*
+                }
+            }
+
+            pub fn state(&self) -> ::serde_json::Value {
+                match self {
+                    #(Self::#names(m) => m.state(),)*
+                }
+            }
+
+            pub fn subscription(
+                &self,
+                context: crate::module::ModuleContext,
+            ) -> ::iced::Subscription<crate::app::AppMsg> {
+                match self {
+                    #(Self::#names(m) => m.subscription(context).map(|(c, e)| {
+                        crate::app::AppMsg::ModuleRunner(ModuleRunnerMsg::#names(c, e))
+                    }),)*
+                }
+            }
+
+            pub fn has_config(&self, config: &ModuleConfig) -> bool {
+                match (self, config) {
+                    #((Self::#names(m), ModuleConfig::#names(c, _)) => m.config() == c,)*
+                    #[allow(unreachable_patterns)]
+                    _ => false,
+                }
+            }
+
+            pub fn set_actions(&mut self, actions: crate::module::actions::ModuleActions) {
+                match self {
+                    #(Self::#names(m) => m.set_actions(actions),)*
+                }
+            }
+
+            pub fn id(&self) -> &::iced::widget::container::Id {
+                match self {
+                    #(Self::#names(m) => m.id(),)*
+                }
+            }
+
+            pub fn has_popup(&self) -> bool {
+                match self {
+                    #(Self::#names(m) => m.has_popup(),)*
+                }
+            }
+
+            pub fn close_popup(&mut self) {
+                match self {
+                    #(Self::#names(m) => m.close_popup(),)*
+                }
+            }
+
+            pub fn tooltip(&self) -> Option<String> {
+                match self {
+                    #(Self::#names(m) => m.tooltip(),)*
+                }
+            }
+
+            pub fn popup(
+                &self,
+            ) -> Option<::iced::Element<'_, crate::app::AppMsg, ::iced::Theme, ::iced::Renderer>>
+            {
+                match self {
+                    #(Self::#names(m) => {
+                        let config = m.config();
+                        m.popup().map(|p| {
+                            p.map(move |e| {
+                                crate::app::AppMsg::ModuleWidget(ModuleWidgetMsg::#names(
+                                    config.clone(),
+                                    e,
+                                ))
+                            })
+                        })
+                    })*
+                }
+            }
+        }
+
+        /// Widget events, which is also what IPC clients can send to the modules
+        #[derive(Debug, Clone, ::serde::Deserialize)]
+        #vis enum ModuleEvent #bounds {
+            #(#names(crate::module::WidgetEvent<#tys>),)*
+        }
+
+        #(
+            impl From<crate::module::WidgetEvent<#tys>> for ModuleEvent #bounds {
+                fn from(event: crate::module::WidgetEvent<#tys>) -> Self {
+                    Self::#names(event)
+                }
+            }
+        )*
+
+        /// Widget event from the UI, along with the config of the module it came from,
+        /// which tells apart several instances of the same module
+        #[derive(Debug, Clone)]
+        #vis enum ModuleWidgetMsg #bounds {
+            #(#names(<#tys as crate::module::Module>::Config, crate::module::WidgetEvent<#tys>),)*
+        }
+
+        /// Bound mouse input on a module, along with its config, see [`ModuleWidgetMsg`]
+        #[derive(Debug, Clone)]
+        #vis enum ModuleActionMsg #bounds {
+            #(#names(<#tys as crate::module::Module>::Config, crate::module::actions::MouseTrigger),)*
+        }
+
+        /// Report of a running module, along with its config, see [`ModuleWidgetMsg`]
+        #[derive(Debug, Clone)]
+        #vis enum ModuleRunnerMsg #bounds {
+            #(#names(<#tys as crate::module::Module>::Config, crate::module::ModuleRunnerEvent<#tys>),)*
+        }
+
+        /// Config of a module instance, followed by its optional mouse bindings
+        #[derive(Clone, ::serde::Serialize, ::serde::Deserialize)]
+        #vis enum ModuleConfig #bounds {
+            #(#names(
+                <#tys as crate::module::Module>::Config,
+                #[serde(
+                    default,
+                    skip_serializing_if = "crate::module::actions::ModuleActions::is_empty"
+                )]
+                crate::module::actions::ModuleActions,
+            ),)*
+        }
+
+        impl ModuleConfig #bounds {
+            pub fn actions(&self) -> &crate::module::actions::ModuleActions {
+                match self {
+                    #(Self::#names(_, actions) => actions,)*
+                }
+            }
+        }
+
+        // Instances are told apart by the module config alone, bindings don't make a new one
+        impl PartialEq for ModuleConfig #bounds {
+            fn eq(&self, other: &Self) -> bool {
+                match (self, other) {
+                    #((Self::#names(a, _), Self::#names(b, _)) => a == b,)*
+                    #[allow(unreachable_patterns)]
+                    _ => false,
+                }
+            }
+        }
+
+        impl Eq for ModuleConfig #bounds {}
+
+        impl ::std::hash::Hash for ModuleConfig #bounds {
+            fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                ::std::mem::discriminant(self).hash(state);
+
+                match self {
+                    #(Self::#names(c, _) => c.hash(state),)*
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for ModuleConfig #bounds {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match self {
+                    #(Self::#names(c, _) => ::std::fmt::Display::fmt(c, f),)*
+                }
+            }
+        }
+
+        impl From<ModuleConfig> for #ident #bounds {
+            fn from(config: ModuleConfig) -> Self {
+                match config {
+                    #(ModuleConfig::#names(c, actions) => {
+                        Self::#names(crate::module::ModuleInfo::new(#name_strs, c, actions))
+                    })*
+                }
+            }
+        }
+
+        #(
+            impl crate::module::ModuleGetSet<#tys> for crate::module::Modules #bounds {
+                fn get(&self) -> impl Iterator<Item = &crate::module::ModuleInfo<#tys>> {
+                    self.modules.iter().filter_map(|m| match m {
+                        #ident::#names(m) => Some(m),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    })
+                }
+
+                fn get_mut(
+                    &mut self,
+                ) -> impl Iterator<Item = &mut crate::module::ModuleInfo<#tys>> {
+                    self.modules.iter_mut().filter_map(|m| match m {
+                        #ident::#names(m) => Some(m),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    })
+                }
+            }
+
+            impl crate::module::ModuleGetSet<#tys> for crate::module::ModuleGroups #bounds {
+                fn get(&self) -> impl Iterator<Item = &crate::module::ModuleInfo<#tys>> {
+                    crate::module::ModuleGetSet::<#tys>::get(&self.left)
+                        .chain(crate::module::ModuleGetSet::<#tys>::get(&self.center))
+                        .chain(crate::module::ModuleGetSet::<#tys>::get(&self.right))
+                }
+
+                fn get_mut(
+                    &mut self,
+                ) -> impl Iterator<Item = &mut crate::module::ModuleInfo<#tys>> {
+                    crate::module::ModuleGetSet::<#tys>::get_mut(&mut self.left)
+                        .chain(crate::module::ModuleGetSet::<#tys>::get_mut(&mut self.center))
+                        .chain(crate::module::ModuleGetSet::<#tys>::get_mut(&mut self.right))
+                }
+            }
+        )*
+
+        impl crate::module::ModuleGroups #bounds {
+            pub fn set_event(&mut self, event: impl Into<ModuleEvent>) {
+                match event.into() {
+                    #(ModuleEvent::#names(e) => {
+                        crate::module::ModuleGetSet::<#tys>::set_event(self, e)
+                    })*
+                }
+            }
+
+            /// Sets a UI event on the module instance it came from
+            pub fn set_widget_event(&mut self, msg: ModuleWidgetMsg) {
+                match msg {
+                    #(ModuleWidgetMsg::#names(c, e) => {
+                        if let Some(m) = crate::module::ModuleGetSet::<#tys>::get_mut(self)
+                            .find(|m| m.config() == &c)
+                        {
+                            m.event = Some(e);
+                        }
+                    })*
+                }
+            }
+
+            /// Acts on bound mouse input on the module instance it came from
+            pub fn trigger(&mut self, msg: ModuleActionMsg) -> Option<crate::app::AppMsg> {
+                match msg {
+                    #(ModuleActionMsg::#names(c, trigger) => {
+                        crate::module::ModuleGetSet::<#tys>::get_mut(self)
+                            .find(|m| m.config() == &c)?
+                            .trigger(trigger)
+                    })*
+                }
+            }
+
+            /// Passes what a running module reported on to its widget
+            pub fn handle(&mut self, msg: ModuleRunnerMsg) {
+                match msg {
+                    #(ModuleRunnerMsg::#names(c, e) => {
+                        if let Some(m) = crate::module::ModuleGetSet::<#tys>::get_mut(self)
+                            .find(|m| m.config() == &c)
+                        {
+                            m.handle(e)
+                        }
+                    })*
+                }
+            }
+        }
+    })
+}
+
+/// Modules registered by the enum, collecting the errors of all the variants at once
+fn variants(input: &DeriveInput) -> syn::Result<Vec<ModuleVariant>> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "`Modules` can only be derived for an enum with one variant per module",
+        ));
+    };
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "the modules enum can't be generic",
+        ));
+    }
+
+    if data.variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "register at least one module, e.g. `Clock(ModuleInfo<Clock>)`",
+        ));
+    }
+
+    let mut errors = Vec::new();
+    let mut modules: Vec<ModuleVariant> = Vec::new();
+
+    for variant in &data.variants {
+        let field = match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0],
+            _ => {
+                errors.push(syn::Error::new_spanned(
+                    variant,
+                    format!(
+                        "expected a single `ModuleInfo<Module>` field, e.g. `{0}(ModuleInfo<{0}>)`",
+                        variant.ident
+                    ),
+                ));
+                continue;
+            }
+        };
+
+        let Some(ty) = module_type(&field.ty) else {
+            errors.push(syn::Error::new_spanned(
+                &field.ty,
+                "expected `ModuleInfo<Module>`",
+            ));
+            continue;
+        };
+
+        let ty_str = ty.to_token_stream().to_string();
+        if let Some(other) = modules
+            .iter()
+            .find(|m| m.ty.to_token_stream().to_string() == ty_str)
+        {
+            errors.push(syn::Error::new_spanned(
+                ty,
+                format!("`{ty_str}` is already registered as `{}`", other.name),
+            ));
+            continue;
+        }
+
+        modules.push(ModuleVariant {
+            name: variant.ident.clone(),
+            ty: ty.clone(),
+        });
+    }
+
+    match errors.into_iter().reduce(|mut all, err| {
+        all.combine(err);
+        all
+    }) {
+        Some(err) => Err(err),
+        None => Ok(modules),
+    }
+}
+
+/// `Module` out of `ModuleInfo<Module>`
+fn module_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+    if segment.ident != "ModuleInfo" {
+        return None;
+    }
+
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+
+    match args.args.iter().collect::<Vec<_>>()[..] {
+        [GenericArgument::Type(ty)] => Some(ty),
+        _ => None,
+    }
+}
@@ -0,0 +1,8 @@
+//! Errors the `Modules` derive reports for enums it can't register.
+//! Run with `TRYBUILD=overwrite` to update the expected output after changing a message
+
+#[test]
+fn compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,15 @@
+use rbar_macros::Modules;
+
+pub struct ModuleInfo<M>(M);
+pub struct Clock;
+pub struct Battery;
+pub struct Audio;
+
+#[derive(Modules)]
+pub enum AnyModule {
+    Clock,
+    Battery(ModuleInfo<Battery>, u32),
+    Audio(Box<Audio>),
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use rbar_macros::Modules;
+
+pub struct ModuleInfo<M>(M);
+pub struct Clock;
+
+#[derive(Modules)]
+pub enum AnyModule {
+    Clock(ModuleInfo<Clock>),
+    OtherClock(ModuleInfo<Clock>),
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use rbar_macros::Modules;
+
+#[derive(Modules)]
+pub enum AnyModule {}
+
+fn main() {}
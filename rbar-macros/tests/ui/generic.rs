@@ -0,0 +1,12 @@
+use rbar_macros::Modules;
+
+pub struct ModuleInfo<M>(M);
+pub struct Clock;
+
+#[derive(Modules)]
+pub enum AnyModule<T> {
+    Clock(ModuleInfo<Clock>),
+    Other(T),
+}
+
+fn main() {}
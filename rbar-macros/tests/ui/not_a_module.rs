@@ -0,0 +1,24 @@
+extern crate self as iced;
+
+#[path = "support/mock.rs"]
+mod mock;
+
+pub use mock::iced::*;
+
+mod app {
+    pub use crate::mock::AppMsg;
+}
+
+mod module {
+    pub use crate::mock::*;
+
+    /// Doesn't implement `Module`
+    pub struct Clock;
+
+    #[derive(rbar_macros::Modules)]
+    pub enum AnyModule {
+        Clock(ModuleInfo<Clock>),
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use rbar_macros::Modules;
+
+#[derive(Modules)]
+pub struct AnyModule {
+    clock: u32,
+}
+
+fn main() {}
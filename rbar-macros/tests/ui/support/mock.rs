@@ -0,0 +1,201 @@
+//! Minimal stand-ins for what the `Modules` expansion refers to in the app and in iced,
+//! so a fixture only has to declare its modules
+
+#![allow(dead_code, non_snake_case)]
+
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+pub mod iced {
+    use std::marker::PhantomData;
+
+    pub struct Theme;
+    pub struct Renderer;
+
+    pub struct Element<'a, Message, Theme, Renderer>(
+        pub PhantomData<&'a (Message, Theme, Renderer)>,
+    );
+
+    impl<'a, Message, Theme, Renderer> Element<'a, Message, Theme, Renderer> {
+        pub fn map<B>(self, _f: impl Fn(Message) -> B + 'a) -> Element<'a, B, Theme, Renderer> {
+            Element(PhantomData)
+        }
+    }
+
+    pub struct Subscription<T>(pub PhantomData<T>);
+
+    impl<T> Subscription<T> {
+        pub fn map<B>(self, _f: impl Fn(T) -> B) -> Subscription<B> {
+            Subscription(PhantomData)
+        }
+    }
+
+    pub mod widget {
+        pub mod container {
+            pub struct Id;
+        }
+    }
+}
+
+/// The messages take anything, the modules' own ones only exist once they all check out
+pub struct AppMsg;
+
+impl AppMsg {
+    pub fn ModuleRunner<T>(_msg: T) -> Self {
+        Self
+    }
+
+    pub fn ModuleWidget<T>(_msg: T) -> Self {
+        Self
+    }
+
+    pub fn ModuleAction<T>(_msg: T) -> Self {
+        Self
+    }
+}
+
+pub mod actions {
+    use serde::{Deserialize, Serialize};
+
+    use super::iced::{Element, Renderer, Theme};
+
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    pub struct ModuleActions;
+
+    impl ModuleActions {
+        pub fn is_empty(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct MouseTrigger;
+
+    pub fn bindings<'a, Message>(
+        content: Element<'a, Message, Theme, Renderer>,
+        _actions: &ModuleActions,
+        _on_trigger: impl Fn(MouseTrigger) -> Message + 'a,
+    ) -> Element<'a, Message, Theme, Renderer> {
+        content
+    }
+}
+
+pub trait Module: Sized + 'static {
+    type Config: std::fmt::Debug
+        + std::fmt::Display
+        + Clone
+        + Eq
+        + std::hash::Hash
+        + Serialize
+        + DeserializeOwned;
+    type Widget: ModuleWidget<Self>;
+}
+
+pub trait ModuleWidget<M: Module> {
+    type Event: std::fmt::Debug + Clone + DeserializeOwned;
+}
+
+pub type WidgetEvent<M> = <<M as Module>::Widget as ModuleWidget<M>>::Event;
+
+#[derive(Clone, Copy)]
+pub struct ModuleContext;
+
+pub struct ModuleRunnerEvent<M>(PhantomData<M>);
+
+impl<M> Clone for ModuleRunnerEvent<M> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<M> std::fmt::Debug for ModuleRunnerEvent<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ModuleRunnerEvent")
+    }
+}
+
+pub struct ModuleInfo<M: Module> {
+    config: M::Config,
+    pub event: Option<WidgetEvent<M>>,
+}
+
+impl<M: Module> ModuleInfo<M> {
+    pub fn new(_name: &str, config: M::Config, _actions: actions::ModuleActions) -> Self {
+        Self {
+            config,
+            event: None,
+        }
+    }
+
+    pub fn config(&self) -> &M::Config {
+        &self.config
+    }
+
+    pub fn actions(&self) -> &actions::ModuleActions {
+        &actions::ModuleActions
+    }
+
+    pub fn set_actions(&mut self, _actions: actions::ModuleActions) {}
+
+    pub fn id(&self) -> &iced::widget::container::Id {
+        &iced::widget::container::Id
+    }
+
+    pub fn view(&self) -> iced::Element<'_, WidgetEvent<M>, iced::Theme, iced::Renderer> {
+        iced::Element(PhantomData)
+    }
+
+    pub fn tooltip(&self) -> Option<String> {
+        None
+    }
+
+    pub fn popup(&self) -> Option<iced::Element<'_, WidgetEvent<M>, iced::Theme, iced::Renderer>> {
+        None
+    }
+
+    pub fn has_popup(&self) -> bool {
+        false
+    }
+
+    pub fn close_popup(&mut self) {}
+
+    pub fn update(&mut self) -> Option<AppMsg> {
+        None
+    }
+
+    pub fn trigger(&mut self, _trigger: actions::MouseTrigger) -> Option<AppMsg> {
+        None
+    }
+
+    pub fn handle(&mut self, _event: ModuleRunnerEvent<M>) {}
+
+    pub fn state(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+
+    pub fn subscription(
+        &self,
+        _context: ModuleContext,
+    ) -> iced::Subscription<(M::Config, ModuleRunnerEvent<M>)> {
+        iced::Subscription(PhantomData)
+    }
+}
+
+pub trait ModuleGetSet<M: Module> {
+    fn get(&self) -> impl Iterator<Item = &ModuleInfo<M>>;
+
+    fn get_mut(&mut self) -> impl Iterator<Item = &mut ModuleInfo<M>>;
+
+    fn set_event(&mut self, _event: WidgetEvent<M>) {}
+}
+
+pub struct Modules {
+    pub modules: Vec<crate::module::AnyModule>,
+}
+
+pub struct ModuleGroups {
+    pub left: Modules,
+    pub center: Modules,
+    pub right: Modules,
+}
@@ -1,23 +1,34 @@
-use std::{sync::Arc, time::Duration};
-
-use chrono::Local;
-use iced::{Subscription, Task, Theme};
-use iced_layershell::{settings::Settings, to_layer_message, Application};
-use itertools::Itertools;
+use std::path::PathBuf;
+
+use iced::{
+    alignment::Horizontal,
+    event,
+    keyboard::{self, key::Named, Key},
+    mouse,
+    widget::{column, container, mouse_area, opaque, row, Space},
+    Event, Length, Rectangle, Subscription, Task, Theme,
+};
+use iced_layershell::{
+    reexport::{Anchor, Layer},
+    settings::Settings,
+    to_layer_message, Application,
+};
 use miette::IntoDiagnostic;
-use tokio::sync::Mutex;
+use tokio::sync::watch;
 
 use crate::{
     config::Config,
+    dbus::{self, BarState, DbusEvent},
+    ipc::{self, IpcMessage, IpcRequest, IpcResponse},
     module::{
-        audio::{Audio, AudioEvent, AudioInfo},
-        battery::{Battery, BatteryEvent, BatteryInfo},
-        clock::{Clock, ClockEvent},
-        ModuleEvent, ModuleGetSet, ModuleGroups,
+        ModuleActionMsg, ModuleEvent, ModuleGroups, ModuleRunnerMsg, ModuleSettings,
+        ModuleWidgetMsg,
     },
-    util::ResultExt,
 };
 
+/// Space between a popup and the edges of the surface
+const POPUP_PADDING: f32 = 5.0;
+
 pub fn run(config: Config) -> miette::Result<()> {
     let settings = Settings {
         id: Some("com.tukanoidd.rbar".into()),
@@ -35,49 +46,176 @@ pub fn run(config: Config) -> miette::Result<()> {
 }
 
 struct App {
+    config_path: PathBuf,
     module_groups: ModuleGroups,
-
-    battery_info: Option<Arc<BatteryInfo>>,
-    audio_info: Option<Arc<Mutex<AudioInfo>>>,
+    module_settings: ModuleSettings,
+
+    top: bool,
+    size: (u32, u32),
+    popup_height: u32,
+    /// Module whose popup or tooltip hangs from the bar, along with the bounds of its view
+    /// once they're known
+    popup: Option<(container::Id, Option<Rectangle>)>,
+    /// Module view under the pointer, whose tooltip shows while no popup is open
+    hovered: Option<container::Id>,
+    visible: bool,
+
+    dbus_state: Option<watch::Sender<BarState>>,
 }
 
 impl App {
-    async fn init(battery_in_config: bool, audio_in_config: bool) -> miette::Result<AppInit> {
-        let battery_info = match battery_in_config {
-            true => BatteryInfo::init().arc().some()?,
-            false => None,
+    /// Keeps only the most recently opened popup open, asks for the bounds of its module
+    /// to anchor it to, and grows the layer surface to fit it or shrinks it back to the bar.
+    /// The pinned layer shell runtime drives a single surface, so popups live in the bar's own,
+    /// which spans the whole width of the output: the strip next to the popup takes the clicks
+    /// meant for the windows under it, closing the popup rather than reaching them.
+    /// Tooltips take the same way while no popup is open, as the bar alone is too short for them
+    fn sync_popup(&mut self) -> Task<AppMsg> {
+        let open = self.module_groups.open_popups();
+        let current = self.popup.as_ref().map(|(id, _)| id);
+
+        let owner = open
+            .iter()
+            .find(|id| Some(*id) != current)
+            .or(open.first())
+            .cloned();
+
+        if open.len() > 1 {
+            self.module_groups.close_popups(owner.as_ref());
+        }
+
+        let owner = owner.or_else(|| {
+            self.hovered
+                .clone()
+                .filter(|id| self.module_groups.tooltip(id).is_some())
+        });
+
+        if owner.as_ref() == current {
+            return Task::none();
+        }
+
+        let was_open = self.popup.is_some();
+        self.popup = owner.map(|id| (id, None));
+
+        let anchor = match &self.popup {
+            Some((id, _)) => {
+                let id = id.clone();
+                container::visible_bounds(id.clone())
+                    .map(move |bounds| AppMsg::PopupAnchor(id.clone(), bounds))
+            }
+            None => Task::none(),
+        };
+
+        match was_open != self.popup.is_some() && self.visible {
+            true => Task::batch([anchor, Task::done(AppMsg::SizeChange(self.surface_size()))]),
+            false => anchor,
+        }
+    }
+
+    fn surface_size(&self) -> (u32, u32) {
+        let (width, height) = self.size;
+
+        match self.popup.is_some() {
+            true => (width, height + self.popup_height),
+            false => (width, height),
+        }
+    }
+
+    /// Layer shell requests that either show the bar, or tuck it away as a 1px surface
+    /// in a corner on the background layer, where it neither covers windows nor reserves space
+    fn visibility_msgs(&self) -> Vec<AppMsg> {
+        match self.visible {
+            true => vec![
+                AppMsg::AnchorChange(Config::anchor(self.top)),
+                AppMsg::LayerChange(Layer::Top),
+                AppMsg::SizeChange(self.surface_size()),
+            ],
+            false => vec![
+                AppMsg::AnchorChange(Anchor::Top | Anchor::Left),
+                AppMsg::LayerChange(Layer::Background),
+                AppMsg::SizeChange((1, 1)),
+            ],
+        }
+    }
+
+    fn set_visible(&mut self, visible: bool) -> Vec<AppMsg> {
+        if visible == self.visible {
+            return vec![];
+        }
+
+        self.visible = visible;
+        self.visibility_msgs()
+    }
+
+    /// Hands the current state to the D-Bus service, which only signals what actually changed
+    fn publish_state(&self) {
+        let Some(dbus_state) = &self.dbus_state else {
+            return;
         };
-        let audio_info = match audio_in_config {
-            true => AudioInfo::init().await.tokio_mutex().arc().some()?,
-            false => None,
+
+        let state = BarState {
+            visible: self.visible,
+            modules: self.module_groups.state(),
         };
 
-        Ok(AppInit {
-            battery_info,
-            audio_info,
-        })
+        dbus_state.send_if_modified(|old| match *old == state {
+            true => false,
+            false => {
+                *old = state;
+                true
+            }
+        });
     }
-}
 
-macro_rules! task_wait_msg {
-    ($ms:expr => $block:block, $msg:ident) => {
-        iced::Task::perform(
-            async {
-                tokio::time::sleep(std::time::Duration::from_millis($ms)).await;
-                $block
-            },
-            AppMsg::$msg,
-        )
-    };
-    (d $duration:expr => $block:block, $msg:ident) => {
-        iced::Task::perform(
-            async move {
-                tokio::time::sleep($duration).await;
-                $block
+    fn reload(&mut self, config: Config) -> Vec<AppMsg> {
+        tracing::info!("Reloading the config from {:?}", config.path);
+
+        if config.size.1 != self.size.1 {
+            tracing::warn!("The reserved space only follows the new bar height after a restart");
+        }
+
+        // Unchanged modules keep running, the rest (re)start through the subscription
+        self.module_groups
+            .reload(config.left, config.center, config.right);
+        self.module_settings =
+            ModuleSettings::new(config.audio_backend, config.retry, config.module_retry);
+        self.top = config.top;
+        self.size = config.size;
+        self.popup_height = config.popup_height;
+        // Modules may have moved, the open popup gets anchored again
+        self.popup = None;
+
+        match self.visible {
+            true => self.visibility_msgs(),
+            false => vec![],
+        }
+    }
+
+    fn handle_ipc(&mut self, request: IpcRequest) -> (IpcResponse, Vec<AppMsg>) {
+        match request {
+            IpcRequest::ToggleVisibility => (IpcResponse::ok(), self.set_visible(!self.visible)),
+            IpcRequest::Show => (IpcResponse::ok(), self.set_visible(true)),
+            IpcRequest::Hide => (IpcResponse::ok(), self.set_visible(false)),
+            IpcRequest::Reload => match Config::load(self.config_path.clone()) {
+                Ok(config) => (IpcResponse::ok(), self.reload(config)),
+                Err(err) => (IpcResponse::error(err), vec![]),
             },
-            AppMsg::$msg,
-        )
-    };
+            IpcRequest::State { module } => {
+                let mut state = self.module_groups.state();
+
+                let response = match module {
+                    None => IpcResponse::state(state.into()),
+                    Some(name) => match state.remove(&name) {
+                        Some(state) => IpcResponse::state(state),
+                        None => IpcResponse::error(format!("Module {name} isn't running")),
+                    },
+                };
+
+                (response, vec![])
+            }
+            IpcRequest::Event { event } => (IpcResponse::ok(), vec![AppMsg::Module(event)]),
+        }
+    }
 }
 
 impl Application for App {
@@ -88,29 +226,30 @@ impl Application for App {
 
     fn new(config: Self::Flags) -> (Self, Task<Self::Message>) {
         let res = Self {
+            config_path: config.path,
             module_groups: (config.left, config.center, config.right).into(),
-
-            battery_info: None,
-            audio_info: None,
+            module_settings: ModuleSettings::new(
+                config.audio_backend,
+                config.retry,
+                config.module_retry,
+            ),
+
+            top: config.top,
+            size: config.size,
+            popup_height: config.popup_height,
+            popup: None,
+            hovered: None,
+            visible: true,
+
+            dbus_state: None,
         };
 
-        let mut tasks = vec![];
-
-        if ModuleGetSet::<Clock>::has(&res.module_groups) {
-            tasks.push(Task::perform(async {}, |_| AppMsg::UpdateTime));
-        }
-
-        let battery_module_in_config = ModuleGetSet::<Battery>::has(&res.module_groups);
-        let audio_module_in_config = ModuleGetSet::<Audio>::has(&res.module_groups);
+        tracing::info!(
+            "Audio modules use the {:?} backend",
+            res.module_settings.audio_backend()
+        );
 
-        tasks.push(Task::perform(
-            Self::init(battery_module_in_config, audio_module_in_config),
-            |res| AppMsg::Init(res.map_err(|e| e.to_string())),
-        ));
-
-        let command = Task::batch(tasks);
-
-        (res, command)
+        (res, Task::none())
     }
 
     fn namespace(&self) -> String {
@@ -118,79 +257,86 @@ impl Application for App {
     }
 
     fn subscription(&self) -> Subscription<Self::Message> {
-        Subscription::none()
+        let ipc = Subscription::run(ipc::listen).map(AppMsg::Ipc);
+        let dbus = Subscription::run(dbus::serve).map(AppMsg::Dbus);
+
+        // The surface takes keyboard focus on demand, i.e. once clicked. Clicks on other
+        // surfaces never reach the bar, so the popup closes once the pointer leaves it instead
+        let close = match self.popup {
+            Some(_) => Subscription::batch([
+                keyboard::on_key_press(|key, _| {
+                    matches!(key, Key::Named(Named::Escape)).then_some(AppMsg::ClosePopup)
+                }),
+                event::listen_with(|event, _, _| {
+                    matches!(event, Event::Mouse(mouse::Event::CursorLeft))
+                        .then_some(AppMsg::ClosePopup)
+                }),
+            ]),
+            None => Subscription::none(),
+        };
+
+        Subscription::batch([
+            self.module_groups.subscription(&self.module_settings),
+            ipc,
+            dbus,
+            close,
+        ])
     }
 
     fn update(&mut self, message: Self::Message) -> Task<Self::Message> {
         let msgs = match message {
-            AppMsg::Init(res) => match res {
-                Ok(AppInit {
-                    battery_info,
-                    audio_info,
-                }) => {
-                    let mut msgs = vec![];
-
-                    if let Some(battery_info) = battery_info {
-                        msgs.push(AppMsg::RefreshBattery(Ok(battery_info)));
-                    }
+            AppMsg::Dbus(DbusEvent::Ready(state)) => {
+                self.dbus_state = Some(state);
+                vec![]
+            }
+            AppMsg::Ipc(IpcMessage { request, reply })
+            | AppMsg::Dbus(DbusEvent::Request(IpcMessage { request, reply })) => {
+                tracing::debug!("IPC request: {request:?}");
 
-                    if let Some(audio_info) = audio_info {
-                        msgs.push(AppMsg::InitAudio(audio_info));
-                    }
+                let (response, msgs) = self.handle_ipc(request);
+                reply.send(response);
 
-                    msgs
-                }
-                Err(err) => {
-                    panic!("Failed to initialize the app: {err}");
-                }
-            },
-            AppMsg::InitAudio(info) => {
-                self.audio_info = Some(info.clone());
-                self.module_groups.set_event(AudioEvent::SetData(info));
-                self.module_groups.update().collect()
+                msgs
             }
 
-            AppMsg::WaitAndMsg(duration, msg) => {
-                let msg = Box::into_inner(msg);
-                return Task::perform(tokio::time::sleep(duration), move |_| msg.clone());
-            }
-            AppMsg::WaitGetBatteryInfo(duration) => {
-                return task_wait_msg!(d duration => {
-                    BatteryInfo::init().arc().err_str()
-                }, RefreshBattery)
+            AppMsg::Module(ev) => {
+                self.module_groups.set_event(ev);
+                self.module_groups.update().collect()
             }
-
-            AppMsg::UpdateTime => {
-                self.module_groups
-                    .set_event(ClockEvent::SetTime(Local::now()));
+            AppMsg::ModuleWidget(msg) => {
+                self.module_groups.set_widget_event(msg);
                 self.module_groups.update().collect()
             }
+            AppMsg::ModuleAction(msg) => self.module_groups.trigger(msg).into_iter().collect(),
+            AppMsg::ModuleRunner(msg) => {
+                self.module_groups.handle(msg);
+                vec![]
+            }
 
-            AppMsg::RefreshBattery(info) => match info {
-                Ok(info) => {
-                    self.battery_info = Some(info);
-                    vec![AppMsg::UpdateBattery]
-                }
-                Err(err) => {
-                    tracing::error!("Failed to refresh battery info: {err}");
-                    vec![AppMsg::wait_ms_get_battery_info(500)]
-                }
-            },
-            AppMsg::UpdateBattery => {
-                let upd = AppMsg::wait_s_get_battery_info(30);
-
-                match &self.battery_info {
-                    None => vec![upd],
-                    Some(battery_info) => {
-                        let data = battery_info.data().collect_vec();
-                        self.module_groups.set_event(BatteryEvent::SetData(data));
-                        self.module_groups.update().chain([upd]).collect()
+            AppMsg::PopupAnchor(id, bounds) => {
+                if let Some((open, anchor)) = &mut self.popup {
+                    if *open == id {
+                        *anchor = bounds;
                     }
                 }
+
+                vec![]
+            }
+            AppMsg::Hover(id, true) => {
+                self.hovered = Some(id);
+                vec![]
+            }
+            AppMsg::Hover(id, false) => {
+                // Leaving one view can come after entering the next one
+                if self.hovered.as_ref() == Some(&id) {
+                    self.hovered = None;
+                }
+
+                vec![]
             }
-            AppMsg::Module(ev) => {
-                self.module_groups.set_event(ev);
-                self.module_groups.update().dedup().collect()
+            AppMsg::ClosePopup => {
+                self.module_groups.close_popups(None);
+                vec![]
             }
 
             AppMsg::AnchorChange(_)
@@ -200,14 +346,73 @@ impl Application for App {
             | AppMsg::VirtualKeyboardPressed { .. } => vec![],
         };
 
-        match msgs.is_empty() {
+        let task = match msgs.is_empty() {
             true => Task::none(),
-            false => Task::batch(msgs.into_iter().dedup().map(|msg| self.update(msg))),
-        }
+            false => Task::batch(msgs.into_iter().map(|msg| {
+                match msg.is_layer_shell() {
+                    // Only the runtime can act on these
+                    true => Task::done(msg),
+                    false => self.update(msg),
+                }
+            })),
+        };
+
+        self.publish_state();
+
+        Task::batch([task, self.sync_popup()])
     }
 
     fn view(&self) -> iced::Element<'_, Self::Message, Self::Theme, iced::Renderer> {
-        self.module_groups.view()
+        if !self.visible {
+            return Space::new(0, 0).into();
+        }
+
+        let bar = container(self.module_groups.view()).height(self.size.1 as f32);
+
+        let popup = self.module_groups.popup().or_else(|| {
+            let (id, _) = self.popup.as_ref()?;
+            self.module_groups.tooltip(id)
+        });
+
+        let Some(popup) = popup else {
+            return bar.into();
+        };
+
+        let (width, max_width) = (self.size.0 as f32, self.size.0 as f32 / 2.0);
+        // Clicks on the popup stay there, the ones anywhere else close it
+        let content = container(opaque(popup.content)).max_width(max_width);
+
+        let anchor = self
+            .popup
+            .as_ref()
+            .filter(|(id, _)| *id == popup.anchor)
+            .and_then(|(_, bounds)| *bounds);
+
+        let popup = match anchor {
+            // Centered under the module, as far as the surface allows
+            Some(bounds) => {
+                let x = (bounds.center_x() - POPUP_PADDING - max_width / 2.0)
+                    .clamp(0.0, (width - 2.0 * POPUP_PADDING - max_width).max(0.0));
+
+                container(row![
+                    Space::with_width(x),
+                    container(content)
+                        .width(max_width)
+                        .align_x(Horizontal::Center)
+                ])
+            }
+            None => container(content).align_x(popup.align_x),
+        }
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(POPUP_PADDING);
+
+        let view = match self.top {
+            true => column![bar, popup],
+            false => column![popup, bar],
+        };
+
+        mouse_area(view).on_press(AppMsg::ClosePopup).into()
     }
 
     fn theme(&self) -> Self::Theme {
@@ -215,57 +420,27 @@ impl Application for App {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct AppInit {
-    battery_info: Option<Arc<BatteryInfo>>,
-    audio_info: Option<Arc<Mutex<AudioInfo>>>,
-}
-
-impl PartialEq for AppInit {
-    fn eq(&self, _other: &Self) -> bool {
-        false
-    }
-}
-
 #[to_layer_message]
 #[derive(Debug, Clone)]
 pub enum AppMsg {
-    Init(Result<AppInit, String>),
-    InitAudio(Arc<Mutex<AudioInfo>>),
-
-    UpdateTime,
-
-    WaitAndMsg(Duration, Box<AppMsg>),
-    WaitGetBatteryInfo(Duration),
-
-    RefreshBattery(Result<Arc<BatteryInfo>, String>),
-    UpdateBattery,
+    Ipc(IpcMessage),
+    Dbus(DbusEvent),
 
+    /// Widget event for every instance of a module, e.g. from IPC
     Module(ModuleEvent),
-}
-
-impl PartialEq for AppMsg {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (AppMsg::Init(ai1), AppMsg::Init(ai2)) => ai1 == ai2,
-            (AppMsg::InitAudio(_), AppMsg::InitAudio(_)) => true,
-            (AppMsg::UpdateTime, AppMsg::UpdateTime) => true,
-            (AppMsg::WaitAndMsg(d1, m1), AppMsg::WaitAndMsg(d2, m2)) => d1 == d2 && m1 == m2,
-            (AppMsg::WaitGetBatteryInfo(d1), AppMsg::WaitGetBatteryInfo(d2)) => d1 == d2,
-            (AppMsg::RefreshBattery(bi1), AppMsg::RefreshBattery(bi2)) => bi1 == bi2,
-            (AppMsg::UpdateBattery, AppMsg::UpdateBattery) => true,
-            (AppMsg::Module(me1), AppMsg::Module(me2)) => me1 == me2,
-            (AppMsg::AnchorChange(a1), AppMsg::AnchorChange(a2)) => a1 == a2,
-            (AppMsg::LayerChange(l1), AppMsg::LayerChange(l2)) => l1 == l2,
-            (AppMsg::MarginChange(m1), AppMsg::MarginChange(m2)) => m1 == m2,
-            (AppMsg::SizeChange(s1), AppMsg::SizeChange(s2)) => s1 == s2,
-            (
-                AppMsg::VirtualKeyboardPressed { time: t1, key: k1 },
-                AppMsg::VirtualKeyboardPressed { time: t2, key: k2 },
-            ) => t1 == t2 && k1 == k2,
-            _ => false,
-        }
-    }
+    /// Widget event from the UI of a single module instance
+    ModuleWidget(ModuleWidgetMsg),
+    /// Bound mouse input on a single module instance
+    ModuleAction(ModuleActionMsg),
+    /// Report from a module running in the background
+    ModuleRunner(ModuleRunnerMsg),
+
+    /// Pointer entering (`true`) or leaving a module view, by its id
+    Hover(container::Id, bool),
+    /// Bounds of the module view the open popup hangs from
+    PopupAnchor(container::Id, Option<Rectangle>),
+    /// Click outside of the open popup, Escape, or the pointer leaving the bar
+    ClosePopup,
 }
 
 impl<T> From<T> for AppMsg
@@ -278,15 +453,14 @@ where
 }
 
 impl AppMsg {
-    pub fn wait_ms_msg(ms: u64, msg: impl Into<Self>) -> Self {
-        Self::WaitAndMsg(Duration::from_millis(ms), Box::new(msg.into()))
-    }
-
-    pub fn wait_ms_get_battery_info(ms: u64) -> Self {
-        Self::WaitGetBatteryInfo(Duration::from_millis(ms))
-    }
-
-    pub fn wait_s_get_battery_info(s: u64) -> Self {
-        Self::WaitGetBatteryInfo(Duration::from_secs(s))
+    fn is_layer_shell(&self) -> bool {
+        matches!(
+            self,
+            AppMsg::AnchorChange(_)
+                | AppMsg::LayerChange(_)
+                | AppMsg::MarginChange(_)
+                | AppMsg::SizeChange(_)
+                | AppMsg::VirtualKeyboardPressed { .. }
+        )
     }
 }
@@ -1,24 +1,41 @@
-use std::{io::Write, path::PathBuf};
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
 
 use directories::ProjectDirs;
-use iced_layershell::{reexport::Anchor, settings::LayerShellSettings};
+use iced_layershell::{
+    reexport::{Anchor, Layer},
+    settings::LayerShellSettings,
+};
 use itertools::Itertools;
 use miette::IntoDiagnostic;
 use serde::{Deserialize, Serialize};
 use smart_default::SmartDefault;
 
-use crate::module::{clock::ClockFormat, ModuleConfig};
+use crate::module::{audio::AudioBackend, clock::ClockFormat, retry::RetryPolicy, ModuleConfig};
 
 #[derive(SmartDefault, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// Where the config was loaded from, used to reload it
+    #[serde(skip)]
+    pub path: PathBuf,
+
     #[default = true]
     pub top: bool,
     #[default((900, 50))]
     pub size: (u32, u32),
+    /// Extra height the bar surface grows by while a module popup or tooltip is open.
+    /// The surface spans the whole width, so the free space next to the popup takes clicks too
+    #[default = 300]
+    pub popup_height: u32,
+    /// Sound server used by the audio and microphone modules
+    pub audio_backend: AudioBackend,
+    /// How the modules retry their data sources and how often they refresh
+    pub retry: RetryPolicy,
+    /// Per module overrides of `retry`, by module name, e.g. `{"Battery": (refresh_ms: Some(5000))}`
+    pub module_retry: BTreeMap<String, RetryPolicy>,
 
     pub left: Vec<ModuleConfig>,
-    #[default(vec![ModuleConfig::Clock(Default::default())])]
+    #[default(vec![ModuleConfig::Clock(Default::default(), Default::default())])]
     pub center: Vec<ModuleConfig>,
     pub right: Vec<ModuleConfig>,
 }
@@ -34,58 +51,70 @@ impl Config {
                     std::fs::create_dir_all(config_dir).into_diagnostic()?;
                 }
 
-                config_dir.join("config.ron")
+                Self::default_path(project_dirs)
             }
         };
 
         match path.exists() {
-            true => {
-                let config: Config =
-                    ron::from_str(&std::fs::read_to_string(path).into_diagnostic()?)
-                        .into_diagnostic()?;
-
-                let duplicates = config
-                    .left
-                    .iter()
-                    .chain(config.center.iter())
-                    .chain(config.right.iter())
-                    .duplicates()
-                    .map(ToString::to_string)
-                    .collect_vec();
-
-                if !duplicates.is_empty() {
-                    return Err(miette::miette!("rbar doesn't support more than one instance of a module running (for now), please remove the duplicates: [{}]", duplicates.join(", ")))              ;
-                }
-
-                Ok(config)
-            }
+            true => Self::load(path),
             false => {
                 tracing::warn!("Config file {path:?} doesn't exist, creating default...");
 
-                let config = Config::default();
+                let mut config = Config::default();
                 let config_str = ron::to_string(&config).into_diagnostic()?;
 
-                let mut file = std::fs::File::create(path).into_diagnostic()?;
+                let mut file = std::fs::File::create(&path).into_diagnostic()?;
                 file.write_all(config_str.as_bytes()).into_diagnostic()?;
 
+                config.path = path;
                 Ok(config)
             }
         }
     }
 
+    pub fn default_path(project_dirs: &ProjectDirs) -> PathBuf {
+        project_dirs.config_dir().join("config.ron")
+    }
+
+    pub fn load(path: PathBuf) -> miette::Result<Self> {
+        let mut config: Config =
+            ron::from_str(&std::fs::read_to_string(&path).into_diagnostic()?).into_diagnostic()?;
+
+        let duplicates = config
+            .left
+            .iter()
+            .chain(config.center.iter())
+            .chain(config.right.iter())
+            .duplicates()
+            .map(ToString::to_string)
+            .collect_vec();
+
+        if !duplicates.is_empty() {
+            return Err(miette::miette!("rbar doesn't support more than one instance of a module running (for now), please remove the duplicates: [{}]", duplicates.join(", ")));
+        }
+
+        config.path = path;
+        Ok(config)
+    }
+
+    /// Edges the bar surface sticks to
+    pub fn anchor(top: bool) -> Anchor {
+        (match top {
+            true => Anchor::Top,
+            false => Anchor::Bottom,
+        }) | Anchor::Left
+            | Anchor::Right
+    }
+
     pub fn layer_shell_settings(&self) -> LayerShellSettings {
         let Self { top, size, .. } = self;
 
         LayerShellSettings {
-            anchor: (match top {
-                true => Anchor::Top,
-                false => Anchor::Bottom,
-            }) | Anchor::Left
-                | Anchor::Right,
-            layer: iced_layershell::reexport::Layer::Top,
+            anchor: Self::anchor(*top),
+            layer: Layer::Top,
             exclusive_zone: size.1 as i32,
             size: Some(*size),
-            keyboard_interactivity: iced_layershell::reexport::KeyboardInteractivity::None,
+            keyboard_interactivity: iced_layershell::reexport::KeyboardInteractivity::OnDemand,
             ..Default::default()
         }
     }
@@ -0,0 +1,237 @@
+//! D-Bus service of a running bar.
+//!
+//! Owns `com.tukanoidd.rbar` on the session bus and serves:
+//! - `/com/tukanoidd/rbar` with `com.tukanoidd.rbar.Bar`: the `Visible` property
+//!   and the `Show`, `Hide`, `ToggleVisibility` and `Reload` methods
+//! - `/com/tukanoidd/rbar/modules/<Module>` with `com.tukanoidd.rbar.Module`:
+//!   the `Name` of the module and its `State` as JSON (same as `rbar msg state <Module>`).
+//!   Further instances of a module are at `<Module>_2`, `<Module>_3` and so on,
+//!   their `Name` being the `<Module>#2` the IPC state goes by
+//!
+//! Properties emit `PropertiesChanged` whenever the bar updates them.
+
+use std::collections::HashMap;
+
+use iced::futures::{channel::mpsc::Sender, SinkExt, Stream};
+use miette::IntoDiagnostic;
+use tokio::sync::watch;
+use zbus::{fdo, object_server::ObjectServer};
+
+use crate::ipc::{IpcMessage, IpcRequest};
+
+const NAME: &str = "com.tukanoidd.rbar";
+const PATH: &str = "/com/tukanoidd/rbar";
+const MODULES_PATH: &str = "/com/tukanoidd/rbar/modules";
+
+/// Everything the service exposes, published by the app after each update
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarState {
+    pub visible: bool,
+    pub modules: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+pub enum DbusEvent {
+    /// The service is up and follows the bar state sent through this channel
+    Ready(watch::Sender<BarState>),
+    /// A method call, handled the same way as IPC requests
+    Request(IpcMessage),
+}
+
+pub fn serve() -> impl Stream<Item = DbusEvent> {
+    iced::stream::channel(16, |mut output| async move {
+        let (states_tx, states) = watch::channel(BarState {
+            visible: true,
+            modules: Default::default(),
+        });
+
+        if output.send(DbusEvent::Ready(states_tx)).await.is_err() {
+            return;
+        }
+
+        if let Err(err) = run(output, states).await {
+            tracing::error!("D-Bus service stopped: {err}");
+        }
+    })
+}
+
+async fn run(
+    output: Sender<DbusEvent>,
+    mut states: watch::Receiver<BarState>,
+) -> miette::Result<()> {
+    let connection = zbus::connection::Builder::session()
+        .into_diagnostic()?
+        .name(NAME)
+        .into_diagnostic()?
+        .serve_at(
+            PATH,
+            Bar {
+                visible: true,
+                requests: output,
+            },
+        )
+        .into_diagnostic()?
+        .build()
+        .await
+        .into_diagnostic()?;
+
+    tracing::info!("Registered {NAME} on the session bus");
+
+    let object_server = connection.object_server();
+    // Module name to the state JSON it's currently published with
+    let mut published = HashMap::new();
+
+    loop {
+        let state = states.borrow_and_update().clone();
+        publish(object_server, &state, &mut published).await?;
+
+        if states.changed().await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+async fn publish(
+    object_server: &ObjectServer,
+    state: &BarState,
+    published: &mut HashMap<String, String>,
+) -> miette::Result<()> {
+    let bar = object_server
+        .interface::<_, Bar>(PATH)
+        .await
+        .into_diagnostic()?;
+
+    if bar.get().await.visible != state.visible {
+        let mut iface = bar.get_mut().await;
+        iface.visible = state.visible;
+        iface
+            .visible_changed(bar.signal_context())
+            .await
+            .into_diagnostic()?;
+    }
+
+    for (name, module_state) in &state.modules {
+        let path = module_path(name);
+        let module_state = module_state.to_string();
+
+        match published.get(name) {
+            None => {
+                object_server
+                    .at(
+                        path.as_str(),
+                        Module {
+                            name: name.clone(),
+                            state: module_state.clone(),
+                        },
+                    )
+                    .await
+                    .into_diagnostic()?;
+            }
+            Some(old) if *old != module_state => {
+                let module = object_server
+                    .interface::<_, Module>(path.as_str())
+                    .await
+                    .into_diagnostic()?;
+
+                let mut iface = module.get_mut().await;
+                iface.state = module_state.clone();
+                iface
+                    .state_changed(module.signal_context())
+                    .await
+                    .into_diagnostic()?;
+            }
+            Some(_) => continue,
+        }
+
+        published.insert(name.clone(), module_state);
+    }
+
+    let removed = published
+        .keys()
+        .filter(|name| !state.modules.contains_key(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for name in removed {
+        object_server
+            .remove::<Module, _>(module_path(&name).as_str())
+            .await
+            .into_diagnostic()?;
+
+        published.remove(&name);
+    }
+
+    Ok(())
+}
+
+/// Object path of a module by its state key, `#` not being allowed in paths
+fn module_path(name: &str) -> String {
+    format!("{MODULES_PATH}/{}", name.replace('#', "_"))
+}
+
+struct Bar {
+    visible: bool,
+    requests: Sender<DbusEvent>,
+}
+
+impl Bar {
+    async fn request(&self, request: IpcRequest) -> fdo::Result<()> {
+        let (message, response) = IpcMessage::new(request);
+
+        self.requests
+            .clone()
+            .send(DbusEvent::Request(message))
+            .await
+            .map_err(|err| fdo::Error::Failed(err.to_string()))?;
+
+        match response.await {
+            Ok(response) if response.ok => Ok(()),
+            Ok(response) => Err(fdo::Error::Failed(response.error.unwrap_or_default())),
+            Err(_) => Err(fdo::Error::Failed("The request was dropped".into())),
+        }
+    }
+}
+
+#[zbus::interface(name = "com.tukanoidd.rbar.Bar")]
+impl Bar {
+    async fn show(&self) -> fdo::Result<()> {
+        self.request(IpcRequest::Show).await
+    }
+
+    async fn hide(&self) -> fdo::Result<()> {
+        self.request(IpcRequest::Hide).await
+    }
+
+    async fn toggle_visibility(&self) -> fdo::Result<()> {
+        self.request(IpcRequest::ToggleVisibility).await
+    }
+
+    /// Re-reads the config file
+    async fn reload(&self) -> fdo::Result<()> {
+        self.request(IpcRequest::Reload).await
+    }
+
+    #[zbus(property)]
+    async fn visible(&self) -> bool {
+        self.visible
+    }
+}
+
+struct Module {
+    name: String,
+    state: String,
+}
+
+#[zbus::interface(name = "com.tukanoidd.rbar.Module")]
+impl Module {
+    #[zbus(property)]
+    async fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Module state as JSON
+    #[zbus(property)]
+    async fn state(&self) -> String {
+        self.state.clone()
+    }
+}
@@ -0,0 +1,234 @@
+//! Control socket of a running bar.
+//!
+//! The socket lives at `$XDG_RUNTIME_DIR/rbar.sock` and speaks line-delimited JSON:
+//! every line sent to it is one [`IpcRequest`], answered with exactly one [`IpcResponse`] line.
+//!
+//! ```text
+//! -> {"command": "toggle_visibility"}
+//! <- {"ok": true}
+//! -> {"command": "state", "module": "Audio"}
+//! <- {"ok": true, "state": {"connected": true, "default": {...}, ...}}
+//! -> {"command": "event", "event": {"Clock": {"SetFormat": "HH_MM"}}}
+//! <- {"ok": true}
+//! -> {"command": "event", "event": {"Audio": {"Scroll": "Up"}}}
+//! <- {"ok": true}
+//! -> {"command": "reload"}
+//! <- {"ok": false, "error": "..."}
+//! ```
+//!
+//! Commands: `toggle_visibility`, `show`, `hide`, `reload` (re-reads the config file),
+//! `state` (all modules, or the one named by `module`) and `event` (any deserializable `ModuleEvent`).
+//! When a module is in the bar more than once, the instances after the first one go by
+//! `<Module>#2`, `<Module>#3` and so on, counting from the left.
+
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use iced::futures::{channel::mpsc::Sender, SinkExt, Stream};
+use miette::IntoDiagnostic;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::oneshot,
+};
+
+use crate::module::ModuleEvent;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum IpcRequest {
+    ToggleVisibility,
+    Show,
+    Hide,
+    Reload,
+    State {
+        #[serde(default)]
+        module: Option<String>,
+    },
+    Event {
+        event: ModuleEvent,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IpcResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl IpcResponse {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn state(state: serde_json::Value) -> Self {
+        Self {
+            ok: true,
+            state: Some(state),
+            ..Default::default()
+        }
+    }
+
+    pub fn error(error: impl ToString) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Request handed to the app, along with the way back to the client that sent it
+#[derive(Debug, Clone)]
+pub struct IpcMessage {
+    pub request: IpcRequest,
+    pub reply: IpcReply,
+}
+
+impl IpcMessage {
+    pub fn new(request: IpcRequest) -> (Self, oneshot::Receiver<IpcResponse>) {
+        let (reply, response) = oneshot::channel();
+
+        (
+            Self {
+                request,
+                reply: IpcReply::new(reply),
+            },
+            response,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IpcReply(Arc<Mutex<Option<oneshot::Sender<IpcResponse>>>>);
+
+impl IpcReply {
+    fn new(sender: oneshot::Sender<IpcResponse>) -> Self {
+        Self(Arc::new(Mutex::new(Some(sender))))
+    }
+
+    /// Answers the request, only the first response goes through
+    pub fn send(&self, response: IpcResponse) {
+        let sender = self.0.lock().ok().and_then(|mut sender| sender.take());
+
+        if let Some(sender) = sender {
+            let _ = sender.send(response);
+        }
+    }
+}
+
+pub fn socket_path() -> miette::Result<PathBuf> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .ok_or_else(|| miette::miette!("XDG_RUNTIME_DIR is not set"))?;
+
+    Ok(PathBuf::from(runtime_dir).join("rbar.sock"))
+}
+
+/// Accepts clients on the control socket and streams their requests to the app
+pub fn listen() -> impl Stream<Item = IpcMessage> {
+    iced::stream::channel(16, |output| async move {
+        let listener = match bind().await {
+            Ok(listener) => listener,
+            Err(err) => {
+                tracing::error!("Failed to start the IPC server: {err}");
+                return;
+            }
+        };
+
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let output = output.clone();
+
+                    tokio::spawn(async move {
+                        if let Err(err) = serve(stream, output).await {
+                            tracing::warn!("IPC client error: {err}");
+                        }
+                    });
+                }
+                Err(err) => tracing::warn!("Failed to accept an IPC client: {err}"),
+            }
+        }
+    })
+}
+
+async fn bind() -> miette::Result<UnixListener> {
+    let path = socket_path()?;
+
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            return Err(miette::miette!(
+                "Another rbar instance is already listening on {path:?}"
+            ));
+        }
+
+        tracing::debug!("Removing stale IPC socket {path:?}");
+        tokio::fs::remove_file(&path).await.into_diagnostic()?;
+    }
+
+    let listener = UnixListener::bind(&path).into_diagnostic()?;
+    tracing::info!("Listening for IPC clients on {path:?}");
+
+    Ok(listener)
+}
+
+/// Answers requests line by line until the client hangs up
+async fn serve(stream: UnixStream, mut output: Sender<IpcMessage>) -> miette::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await.into_diagnostic()? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<IpcRequest>(&line) {
+            Ok(request) => {
+                let (message, response) = IpcMessage::new(request);
+                output.send(message).await.into_diagnostic()?;
+
+                response
+                    .await
+                    .unwrap_or_else(|_| IpcResponse::error("The request was dropped"))
+            }
+            Err(err) => IpcResponse::error(format!("Invalid request: {err}")),
+        };
+
+        let mut response = serde_json::to_string(&response).into_diagnostic()?;
+        response.push('\n');
+        writer
+            .write_all(response.as_bytes())
+            .await
+            .into_diagnostic()?;
+    }
+
+    Ok(())
+}
+
+/// Sends a single request to the running bar and waits for its response
+pub fn request(request: &serde_json::Value) -> miette::Result<IpcResponse> {
+    let path = socket_path()?;
+    let mut stream = std::os::unix::net::UnixStream::connect(&path)
+        .map_err(|err| miette::miette!("Failed to connect to rbar at {path:?}: {err}"))?;
+
+    let mut line = serde_json::to_string(request).into_diagnostic()?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).into_diagnostic()?;
+
+    let mut response = String::new();
+    std::io::BufReader::new(stream)
+        .read_line(&mut response)
+        .into_diagnostic()?;
+
+    serde_json::from_str(&response).into_diagnostic()
+}
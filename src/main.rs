@@ -1,27 +1,96 @@
-#![feature(box_into_inner)]
-
 mod app;
 mod config;
+mod dbus;
+mod ipc;
 mod module;
 mod util;
 
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use config::Config;
 use directories::ProjectDirs;
+use miette::IntoDiagnostic;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 /// Rusty Bar
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     config: Option<PathBuf>,
-    #[arg(long)]
+    #[arg(long, global = true)]
     debug: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     trace: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the bar (default)
+    Run,
+    /// Send a command to the running bar
+    Msg {
+        #[command(subcommand)]
+        command: MsgCommand,
+    },
+    /// Work with the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum MsgCommand {
+    /// Hide the bar if it's shown, show it otherwise
+    ToggleVisibility,
+    Show,
+    Hide,
+    /// Re-read the config file
+    Reload,
+    /// Print the state of all modules, or of a single one, as JSON
+    State {
+        /// Module name, `Disk#2` and so on for further instances of the same module
+        module: Option<String>,
+    },
+    /// Send an event to a module, e.g. '{"Audio": {"Scroll": "Up"}}'
+    Event {
+        event: String,
+    },
+}
+
+impl MsgCommand {
+    fn request(self) -> miette::Result<serde_json::Value> {
+        let request = match self {
+            MsgCommand::ToggleVisibility => serde_json::json!({ "command": "toggle_visibility" }),
+            MsgCommand::Show => serde_json::json!({ "command": "show" }),
+            MsgCommand::Hide => serde_json::json!({ "command": "hide" }),
+            MsgCommand::Reload => serde_json::json!({ "command": "reload" }),
+            MsgCommand::State { module } => {
+                serde_json::json!({ "command": "state", "module": module })
+            }
+            MsgCommand::Event { event } => {
+                let event: serde_json::Value = serde_json::from_str(&event)
+                    .map_err(|err| miette::miette!("Event isn't valid JSON: {err}"))?;
+
+                serde_json::json!({ "command": "event", "event": event })
+            }
+        };
+
+        Ok(request)
+    }
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Validate the config file without starting the bar
+    Check,
+    /// Print the default config
+    PrintDefault,
 }
 
 fn main() -> miette::Result<()> {
@@ -29,6 +98,7 @@ fn main() -> miette::Result<()> {
         config,
         debug,
         trace,
+        command,
     } = Cli::parse();
 
     init_logging(debug, trace);
@@ -36,9 +106,51 @@ fn main() -> miette::Result<()> {
     let project_dirs = ProjectDirs::from("com", "tukanoidd", "rbar")
         .ok_or_else(|| miette::miette!("Failed to initialize ProjectDirs"))?;
 
-    let config = Config::open(&project_dirs, config)?;
+    match command.unwrap_or(Command::Run) {
+        Command::Run => {
+            let config = Config::open(&project_dirs, config)?;
+
+            app::run(config)
+        }
+        Command::Msg { command } => {
+            let response = ipc::request(&command.request()?)?;
+
+            if !response.ok {
+                return Err(miette::miette!(
+                    "{}",
+                    response.error.as_deref().unwrap_or("Request failed")
+                ));
+            }
+
+            if let Some(state) = response.state {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&state).into_diagnostic()?
+                );
+            }
+
+            Ok(())
+        }
+        Command::Config { command } => match command {
+            ConfigCommand::Check => {
+                let path = config.unwrap_or_else(|| Config::default_path(&project_dirs));
+                Config::load(path.clone())?;
+
+                println!("{path:?} is valid");
+                Ok(())
+            }
+            ConfigCommand::PrintDefault => {
+                let config = ron::ser::to_string_pretty(
+                    &Config::default(),
+                    ron::ser::PrettyConfig::default(),
+                )
+                .into_diagnostic()?;
 
-    app::run(config)
+                println!("{config}");
+                Ok(())
+            }
+        },
+    }
 }
 
 fn init_logging(debug: bool, trace: bool) {
@@ -48,7 +160,11 @@ fn init_logging(debug: bool, trace: bool) {
         .unwrap_or("info");
 
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer().pretty())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .pretty()
+                .with_writer(std::io::stderr),
+        )
         .with(tracing_subscriber::filter::EnvFilter::new(format!(
             "rbar={level}"
         )))
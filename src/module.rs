@@ -1,75 +1,652 @@
+pub mod actions;
 pub mod audio;
+pub mod backlight;
 pub mod battery;
+pub mod button;
 pub mod clock;
-pub mod new;
-
-use std::hash::Hash;
+pub mod cpu;
+pub mod custom;
+pub mod disk;
+pub mod memory;
+pub mod microphone;
+pub mod privacy;
+pub mod retry;
+pub mod temperature;
+pub mod tray;
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    hash::Hash,
+    panic::AssertUnwindSafe,
+};
 
-use bon::Builder;
 use derive_more::derive::{Display, From};
 use iced::{
-    widget::{container, row, Space},
-    Element, Length, Renderer, Theme,
+    alignment::Horizontal,
+    futures::{channel::mpsc::Sender, FutureExt, SinkExt, Stream},
+    widget::{container, mouse_area, row, text, Space},
+    Color, Element, Length, Renderer, Subscription, Theme,
 };
+use iced_fonts::{nerd, Nerd};
 use itertools::Itertools;
+use rbar_macros::Modules;
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{app::AppMsg, util::spawn_command};
+
+use actions::{ModuleAction, ModuleActions, MouseTrigger};
+use audio::{Audio, AudioBackend};
+use backlight::Backlight;
+use battery::Battery;
+use button::Button;
+use clock::Clock;
+use cpu::Cpu;
+use custom::Custom;
+use disk::Disk;
+use memory::Memory;
+use microphone::Microphone;
+use privacy::Privacy;
+use retry::{Backoff, RetryPolicy};
+use temperature::Temperature;
+use tray::Tray;
+
+/// Data side of a module. Owns its data source and runs in the background for as long as
+/// the module is in the bar, handing what it finds out to its [`ModuleWidget`]
+pub trait Module: Sized + Send + 'static {
+    type Config: TModuleConfig;
+    /// Things the module reacts to, coming either from its data source (see [`Module::wait`])
+    /// or from its widget
+    type Event: std::fmt::Debug + Clone + Send;
+    /// Data the widget shows, produced by [`Module::init`] and [`Module::cycle`]
+    type Output: std::fmt::Debug + Clone + Send;
 
-use crate::app::AppMsg;
+    type Widget: ModuleWidget<Self>;
 
-pub trait TModule: std::fmt::Debug {
-    type Config: TModuleConfig;
-    type Event: Clone;
+    fn new(config: Self::Config, context: ModuleContext) -> miette::Result<Self>;
+
+    /// Connects to the data source and reads the initial state
+    fn init(&mut self) -> impl Future<Output = miette::Result<Self::Output>> + Send;
+
+    /// Waits until the data source has something to handle.
+    /// Dropped whenever a widget event comes in first, so it has to be cancel safe
+    fn wait(&mut self) -> impl Future<Output = miette::Result<Self::Event>> + Send;
+
+    /// Handles an event, returning new data for the widget if there is any
+    fn cycle(
+        &mut self,
+        event: Self::Event,
+    ) -> impl Future<Output = miette::Result<Option<Self::Output>>> + Send;
+}
 
-    fn new(config: Self::Config) -> Self;
-    fn update(&mut self, event: Self::Event) -> Option<AppMsg>;
+/// UI side of a module, living in the app
+pub trait ModuleWidget<M>: std::fmt::Debug
+where
+    M: Module,
+{
+    type Event: std::fmt::Debug + Clone + for<'de> Deserialize<'de>;
+
+    fn new(config: M::Config) -> Self;
+    /// Takes in the latest data of the module
+    fn set(&mut self, output: M::Output);
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<M>>;
     fn view(&self) -> Element<'_, Self::Event, Theme, Renderer>;
+
+    /// Extra detail shown while hovering the widget. It hangs from the bar like a popup,
+    /// so it can run over several lines
+    fn tooltip(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the widget wants its popup open, asked after every update without building it
+    fn has_popup(&self) -> bool {
+        false
+    }
+
+    /// Content shown under the widget (or above it, for bottom bars) while `has_popup` holds.
+    /// Only one popup is open at a time, opening one closes the others
+    fn popup(&self) -> Option<Element<'_, Self::Event, Theme, Renderer>> {
+        None
+    }
+
+    /// Closes the popup, after a click outside of it, Escape, or another popup opening
+    fn close_popup(&mut self) {}
+
+    /// Snapshot of what the widget currently shows, for external consumers (IPC)
+    fn state(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+}
+
+pub type WidgetEvent<M> = <<M as Module>::Widget as ModuleWidget<M>>::Event;
+
+pub enum ModuleWidgetUpdateOutput<M>
+where
+    M: Module,
+{
+    App(AppMsg),
+    Widget(WidgetEvent<M>),
+    Module(M::Event),
+}
+
+impl<M> ModuleWidgetUpdateOutput<M>
+where
+    M: Module,
+{
+    pub fn app(msg: impl Into<AppMsg>) -> Self {
+        Self::App(msg.into())
+    }
+
+    pub fn widget(event: impl Into<WidgetEvent<M>>) -> Self {
+        Self::Widget(event.into())
+    }
+
+    pub fn module(event: impl Into<M::Event>) -> Self {
+        Self::Module(event.into())
+    }
 }
 
-pub trait TModuleConfig: Default + Hash + Serialize + for<'de> Deserialize<'de> {}
+pub trait TModuleConfig:
+    std::fmt::Debug
+    + Default
+    + Clone
+    + PartialEq
+    + Hash
+    + Send
+    + Sync
+    + Serialize
+    + for<'de> Deserialize<'de>
+    + 'static
+{
+}
 
-impl<C> TModuleConfig for C where C: Default + Hash + Serialize + for<'de> Deserialize<'de> {}
+impl<C> TModuleConfig for C where
+    C: std::fmt::Debug
+        + Default
+        + Clone
+        + PartialEq
+        + Hash
+        + Send
+        + Sync
+        + Serialize
+        + for<'de> Deserialize<'de>
+        + 'static
+{
+}
 
-#[derive(Default, Display, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Default, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NoConfig;
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct NoEvent;
+/// Module settings from the config that aren't specific to a single module
+#[derive(Debug, Clone)]
+pub struct ModuleSettings {
+    /// Already resolved, never [`AudioBackend::Auto`]
+    audio_backend: AudioBackend,
+    retry: RetryPolicy,
+    retry_overrides: BTreeMap<String, RetryPolicy>,
+}
+
+impl ModuleSettings {
+    pub fn new(
+        audio_backend: AudioBackend,
+        retry: RetryPolicy,
+        retry_overrides: BTreeMap<String, RetryPolicy>,
+    ) -> Self {
+        Self {
+            audio_backend: audio_backend.resolve(),
+            retry,
+            retry_overrides,
+        }
+    }
+
+    pub fn audio_backend(&self) -> AudioBackend {
+        self.audio_backend
+    }
+
+    /// What the named module starts with
+    pub fn context(&self, module: &str) -> ModuleContext {
+        ModuleContext {
+            audio_backend: self.audio_backend,
+            retry: self
+                .retry_overrides
+                .get(module)
+                .copied()
+                .unwrap_or(self.retry),
+        }
+    }
+}
+
+/// Settings a module starts with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModuleContext {
+    /// Already resolved, never [`AudioBackend::Auto`]
+    pub audio_backend: AudioBackend,
+    pub retry: RetryPolicy,
+}
+
+/// What a running module reports back to the app
+pub enum ModuleRunnerEvent<M>
+where
+    M: Module,
+{
+    /// The module listens for events from its widget on this channel
+    Ready(mpsc::UnboundedSender<M::Event>),
+    Output(M::Output),
+    /// The module failed and gets restarted after a while
+    Failed(String),
+}
+
+impl<M> Clone for ModuleRunnerEvent<M>
+where
+    M: Module,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Ready(events) => Self::Ready(events.clone()),
+            Self::Output(output) => Self::Output(output.clone()),
+            Self::Failed(error) => Self::Failed(error.clone()),
+        }
+    }
+}
+
+impl<M> std::fmt::Debug for ModuleRunnerEvent<M>
+where
+    M: Module,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ready(_) => f.write_str("Ready"),
+            Self::Output(output) => f.debug_tuple("Output").field(output).finish(),
+            Self::Failed(error) => f.debug_tuple("Failed").field(error).finish(),
+        }
+    }
+}
+
+/// Keeps the module running until the app drops the subscription,
+/// restarting it according to its [`RetryPolicy`] whenever it fails or panics
+fn run<M>(
+    name: &'static str,
+    config: M::Config,
+    context: ModuleContext,
+) -> impl Stream<Item = ModuleRunnerEvent<M>>
+where
+    M: Module,
+{
+    iced::stream::channel(16, move |mut output| async move {
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        if output
+            .send(ModuleRunnerEvent::Ready(events_tx))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut backoff = Backoff::new(context.retry);
+
+        loop {
+            let running = drive::<M>(
+                config.clone(),
+                context,
+                &mut events,
+                &mut output,
+                &mut backoff,
+            );
+
+            let error = match AssertUnwindSafe(running).catch_unwind().await {
+                Ok(Ok(())) => return,
+                // The context alone would hide the cause, e.g. which file couldn't be read
+                Ok(Err(err)) => err
+                    .chain()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(": "),
+                Err(panic) => format!("panicked: {}", panic_message(&*panic)),
+            };
+
+            let delay = backoff.fail();
+            tracing::debug!(
+                "{name} module failed {} time(s) in a row",
+                backoff.failures()
+            );
+
+            let error = match delay {
+                Some(delay) => {
+                    tracing::error!("{name} module failed, restarting in {delay:?}: {error}");
+                    error
+                }
+                None => {
+                    tracing::error!("{name} module failed, giving up: {error}");
+                    format!("{error} (gave up after {} failures)", backoff.failures())
+                }
+            };
+
+            if output.send(ModuleRunnerEvent::Failed(error)).await.is_err() {
+                return;
+            }
+
+            let Some(delay) = delay else {
+                return;
+            };
+
+            tokio::time::sleep(delay).await;
+
+            // Whatever the widget asked for in the meantime is stale by now
+            while events.try_recv().is_ok() {}
+        }
+    })
+}
+
+/// Creates and initializes the module, then cycles it on every event from its data source
+/// or its widget. Only returns without an error once the app doesn't need the module anymore
+async fn drive<M>(
+    config: M::Config,
+    context: ModuleContext,
+    events: &mut mpsc::UnboundedReceiver<M::Event>,
+    output: &mut Sender<ModuleRunnerEvent<M>>,
+    backoff: &mut Backoff,
+) -> miette::Result<()>
+where
+    M: Module,
+{
+    let mut module = M::new(config, context)?;
+
+    let data = module.init().await?;
+    backoff.reset();
+
+    if output.send(ModuleRunnerEvent::Output(data)).await.is_err() {
+        return Ok(());
+    }
+
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => event,
+                None => return Ok(()),
+            },
+            event = module.wait() => event?,
+        };
+
+        if let Some(data) = module.cycle(event).await? {
+            if output.send(ModuleRunnerEvent::Output(data)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown reason")
+}
+
+/// Runs a piece of widget code, turning a panic into an error instead of taking the bar down
+fn catch<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    std::panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|panic| format!("panicked: {}", panic_message(&*panic)))
+}
+
+/// Stand-in for a failed module, the reason shows up as its tooltip
+fn error_view<'a, Message>() -> Element<'a, Message, Theme, Renderer>
+where
+    Message: 'a,
+{
+    text(nerd::icon_to_string(Nerd::Alert))
+        .font(iced_fonts::NERD_FONT)
+        .style(text::danger)
+        .into()
+}
+
+/// How alarming a reading is, by the thresholds set for it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn of(value: f32, warning: Option<f32>, critical: Option<f32>) -> Self {
+        let reached = |threshold: Option<f32>| threshold.is_some_and(|t| value >= t);
+
+        match (reached(critical), reached(warning)) {
+            (true, _) => Self::Critical,
+            (_, true) => Self::Warning,
+            _ => Self::Normal,
+        }
+    }
+
+    pub fn style(self, theme: &Theme) -> text::Style {
+        match self {
+            Self::Normal => text::default(theme),
+            // The palette has no warning color, this is the yellow of Catppuccin
+            Self::Warning => text::Style {
+                color: Some(Color::from_rgb8(0xf9, 0xe2, 0xaf)),
+            },
+            Self::Critical => text::danger(theme),
+        }
+    }
+}
 
-#[derive(Debug, Builder)]
 pub struct ModuleInfo<M>
 where
-    M: TModule,
+    M: Module,
 {
-    pub module: M,
-    pub event: Option<M::Event>,
+    name: &'static str,
+    config: M::Config,
+    /// Mouse bindings set by the user, on top of what the widget does
+    actions: ModuleActions,
+    pub widget: M::Widget,
+    /// Way to the running module, once it's up
+    events: Option<mpsc::UnboundedSender<M::Event>>,
+    pub event: Option<WidgetEvent<M>>,
+    /// Why the module (or its widget) is broken, shown instead of the widget until new data comes in.
+    /// A cell, since the widget can break while being drawn
+    error: RefCell<Option<String>>,
+    /// Container of the view, whose bounds anchor the popup
+    id: container::Id,
 }
 
 impl<M> ModuleInfo<M>
 where
-    M: TModule,
+    M: Module,
 {
-    pub fn new(module: M) -> Self {
+    pub fn new(name: &'static str, config: M::Config, actions: ModuleActions) -> Self {
         Self {
-            module,
+            name,
+            widget: M::Widget::new(config.clone()),
+            config,
+            actions,
+            events: None,
             event: None,
+            error: RefCell::new(None),
+            id: container::Id::unique(),
+        }
+    }
+
+    pub fn id(&self) -> &container::Id {
+        &self.id
+    }
+
+    pub fn config(&self) -> &M::Config {
+        &self.config
+    }
+
+    pub fn actions(&self) -> &ModuleActions {
+        &self.actions
+    }
+
+    pub fn set_actions(&mut self, actions: ModuleActions) {
+        self.actions = actions;
+    }
+
+    /// Runs the action bound to the mouse input
+    pub fn trigger(&mut self, trigger: MouseTrigger) -> Option<AppMsg> {
+        match self.actions.get(trigger)? {
+            ModuleAction::Command(command) => {
+                spawn_command(command);
+                None
+            }
+            ModuleAction::Event(event) => match serde_json::from_str(event) {
+                Ok(event) => {
+                    self.event = Some(event);
+                    self.update()
+                }
+                Err(err) => {
+                    tracing::error!("{event} isn't a valid {} event: {err}", self.name);
+                    None
+                }
+            },
         }
     }
 
-    pub fn new_conf(c: M::Config) -> Self {
-        Self::new(M::new(c))
+    fn fail(&self, error: String) {
+        tracing::error!("{} widget {error}", self.name);
+        self.error.replace(Some(error));
     }
 
     pub fn update(&mut self) -> Option<AppMsg> {
-        self.event.take().and_then(|e| self.module.update(e))
+        let event = self.event.take()?;
+
+        match catch(|| self.update_widget(event)) {
+            Ok(msg) => msg,
+            Err(error) => {
+                self.fail(error);
+                None
+            }
+        }
+    }
+
+    fn update_widget(&mut self, mut event: WidgetEvent<M>) -> Option<AppMsg> {
+        loop {
+            match self.widget.update(event)? {
+                ModuleWidgetUpdateOutput::App(msg) => return Some(msg),
+                ModuleWidgetUpdateOutput::Widget(next) => event = next,
+                ModuleWidgetUpdateOutput::Module(event) => {
+                    self.send(event);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn send(&self, event: M::Event) {
+        match &self.events {
+            Some(events) => {
+                if events.send(event).is_err() {
+                    tracing::error!("{} module is gone, dropping the event", self.name);
+                }
+            }
+            None => tracing::warn!("{} module isn't ready yet, dropping {event:?}", self.name),
+        }
+    }
+
+    pub fn handle(&mut self, event: ModuleRunnerEvent<M>) {
+        match event {
+            ModuleRunnerEvent::Ready(events) => self.events = Some(events),
+            ModuleRunnerEvent::Output(data) => match catch(|| self.widget.set(data)) {
+                Ok(()) => {
+                    self.error.replace(None);
+                }
+                Err(error) => self.fail(error),
+            },
+            ModuleRunnerEvent::Failed(error) => {
+                self.error.replace(Some(error));
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, WidgetEvent<M>, Theme, Renderer> {
+        let error = self.error.borrow().clone();
+
+        let view = match error {
+            Some(_) => error_view(),
+            None => catch(|| self.widget.view()).unwrap_or_else(|error| {
+                self.fail(error);
+                error_view()
+            }),
+        };
+
+        container(view).id(self.id.clone()).into()
     }
 
-    pub fn view(&self) -> Element<'_, M::Event, Theme, Renderer> {
-        self.module.view()
+    /// Tooltip of the widget, or why the module is broken
+    pub fn tooltip(&self) -> Option<String> {
+        if let Some(error) = self.error.borrow().as_deref() {
+            return Some(format!("{}: {error}", self.name));
+        }
+
+        catch(|| self.widget.tooltip()).unwrap_or_else(|error| {
+            let tooltip = format!("{}: {error}", self.name);
+            self.fail(error);
+            Some(tooltip)
+        })
+    }
+
+    pub fn popup(&self) -> Option<Element<'_, WidgetEvent<M>, Theme, Renderer>> {
+        if self.error.borrow().is_some() {
+            return None;
+        }
+
+        catch(|| self.widget.popup()).unwrap_or_else(|error| {
+            self.fail(error);
+            None
+        })
+    }
+
+    pub fn has_popup(&self) -> bool {
+        if self.error.borrow().is_some() {
+            return false;
+        }
+
+        catch(|| self.widget.has_popup()).unwrap_or_else(|error| {
+            self.fail(error);
+            false
+        })
+    }
+
+    pub fn close_popup(&mut self) {
+        if let Err(error) = catch(|| self.widget.close_popup()) {
+            self.fail(error);
+        }
+    }
+
+    pub fn state(&self) -> serde_json::Value {
+        if let Some(error) = self.error.borrow().as_deref() {
+            return serde_json::json!({ "error": error });
+        }
+
+        catch(|| self.widget.state()).unwrap_or_else(|error| {
+            let state = serde_json::json!({ "error": error });
+            self.fail(error);
+            state
+        })
+    }
+
+    /// The running module. Keeps running across config reloads as long as neither
+    /// its config nor the context change. Reports come with the config,
+    /// so they reach this instance only
+    pub fn subscription(
+        &self,
+        context: ModuleContext,
+    ) -> Subscription<(M::Config, ModuleRunnerEvent<M>)> {
+        Subscription::run_with_id(
+            (TypeId::of::<M>(), self.config.clone(), context),
+            run::<M>(self.name, self.config.clone(), context),
+        )
+        .with(self.config.clone())
     }
 }
 
 pub struct Modules {
-    modules: Vec<Module>,
+    modules: Vec<AnyModule>,
     position: ModulePosition,
 }
 
@@ -85,7 +662,17 @@ impl Modules {
     }
 
     pub fn view(&self) -> Element<'_, AppMsg, Theme, Renderer> {
-        let mut children = self.modules.iter().map(Module::view).collect_vec();
+        let mut children = self
+            .modules
+            .iter()
+            .map(|m| {
+                Element::from(
+                    mouse_area(m.view())
+                        .on_enter(AppMsg::Hover(m.id().clone(), true))
+                        .on_exit(AppMsg::Hover(m.id().clone(), false)),
+                )
+            })
+            .collect_vec();
 
         match self.position {
             ModulePosition::Left => children.push(Space::with_width(Length::Fill).into()),
@@ -103,6 +690,64 @@ impl Modules {
     pub fn update(&mut self) -> impl Iterator<Item = AppMsg> + '_ {
         self.modules.iter_mut().filter_map(|m| m.update())
     }
+
+    fn align_x(&self) -> Horizontal {
+        match self.position {
+            ModulePosition::Left => Horizontal::Left,
+            ModulePosition::Center => Horizontal::Center,
+            ModulePosition::Right => Horizontal::Right,
+        }
+    }
+
+    pub fn popup(&self) -> Option<ModulePopup<'_>> {
+        self.modules.iter().find_map(|m| {
+            m.popup().map(|content| ModulePopup {
+                content,
+                anchor: m.id().clone(),
+                align_x: self.align_x(),
+            })
+        })
+    }
+
+    /// Tooltip of the module with the view `id`, laid out like a popup
+    pub fn tooltip(&self, id: &container::Id) -> Option<ModulePopup<'_>> {
+        let module = self.modules.iter().find(|m| m.id() == id)?;
+        let tip = container(text(module.tooltip()?))
+            .padding(5)
+            .style(container::rounded_box);
+
+        Some(ModulePopup {
+            content: tip.into(),
+            anchor: id.clone(),
+            align_x: self.align_x(),
+        })
+    }
+
+    fn open_popups(&self) -> impl Iterator<Item = &container::Id> + '_ {
+        self.modules
+            .iter()
+            .filter(|m| m.has_popup())
+            .map(AnyModule::id)
+    }
+
+    fn close_popups(&mut self, keep: Option<&container::Id>) {
+        self.modules
+            .iter_mut()
+            .filter(|m| Some(m.id()) != keep)
+            .for_each(AnyModule::close_popup);
+    }
+
+    pub fn state(&self) -> impl Iterator<Item = (&'static str, serde_json::Value)> + '_ {
+        self.modules.iter().map(|m| (m.name(), m.state()))
+    }
+
+    pub fn subscription(&self, settings: &ModuleSettings) -> Subscription<AppMsg> {
+        Subscription::batch(
+            self.modules
+                .iter()
+                .map(|m| m.subscription(settings.context(m.name()))),
+        )
+    }
 }
 
 impl<I> From<I> for Modules
@@ -117,6 +762,15 @@ where
     }
 }
 
+/// An open module popup or a tooltip, along with what it hangs from
+pub struct ModulePopup<'a> {
+    pub content: Element<'a, AppMsg, Theme, Renderer>,
+    /// Id of the module view, whose bounds the popup gets placed under
+    pub anchor: container::Id,
+    /// Alignment by the group of the module, until the bounds of its view are known
+    pub align_x: Horizontal,
+}
+
 pub enum ModulePosition {
     Left,
     Center,
@@ -149,6 +803,104 @@ impl ModuleGroups {
             .chain(self.center.update())
             .chain(self.right.update())
     }
+
+    /// The open module popup, the first one should several be open
+    pub fn popup(&self) -> Option<ModulePopup<'_>> {
+        self.left
+            .popup()
+            .or_else(|| self.center.popup())
+            .or_else(|| self.right.popup())
+    }
+
+    /// Tooltip of the module with the view `id`, if it has one at the moment
+    pub fn tooltip(&self, id: &container::Id) -> Option<ModulePopup<'_>> {
+        self.left
+            .tooltip(id)
+            .or_else(|| self.center.tooltip(id))
+            .or_else(|| self.right.tooltip(id))
+    }
+
+    /// Modules with an open popup, by the id of their view
+    pub fn open_popups(&self) -> Vec<container::Id> {
+        self.left
+            .open_popups()
+            .chain(self.center.open_popups())
+            .chain(self.right.open_popups())
+            .cloned()
+            .collect()
+    }
+
+    /// Closes every popup, except for the one of the module `keep` belongs to
+    pub fn close_popups(&mut self, keep: Option<&container::Id>) {
+        self.left.close_popups(keep);
+        self.center.close_popups(keep);
+        self.right.close_popups(keep);
+    }
+
+    pub fn subscription(&self, settings: &ModuleSettings) -> Subscription<AppMsg> {
+        Subscription::batch([
+            self.left.subscription(settings),
+            self.center.subscription(settings),
+            self.right.subscription(settings),
+        ])
+    }
+
+    /// Rebuilds the groups from a new config, keeping the modules whose config didn't change
+    /// along with what they show and their running data sources
+    pub fn reload(
+        &mut self,
+        left: Vec<ModuleConfig>,
+        center: Vec<ModuleConfig>,
+        right: Vec<ModuleConfig>,
+    ) {
+        let mut old = [&mut self.left, &mut self.center, &mut self.right]
+            .into_iter()
+            .flat_map(|modules| std::mem::take(&mut modules.modules))
+            .collect_vec();
+
+        let mut modules = |configs: Vec<ModuleConfig>| {
+            configs
+                .into_iter()
+                .map(
+                    |config| match old.iter().position(|m| m.has_config(&config)) {
+                        Some(i) => {
+                            let mut module = old.swap_remove(i);
+                            module.set_actions(config.actions().clone());
+                            module
+                        }
+                        None => config.into(),
+                    },
+                )
+                .collect()
+        };
+
+        self.left.modules = modules(left);
+        self.center.modules = modules(center);
+        self.right.modules = modules(right);
+    }
+
+    /// State of every running module, keyed by the module name.
+    /// Further instances of a module are numbered in bar order, e.g. `Disk`, `Disk#2`, `Disk#3`
+    pub fn state(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut instances = HashMap::<&str, usize>::new();
+
+        self.left
+            .state()
+            .chain(self.center.state())
+            .chain(self.right.state())
+            .map(|(name, state)| {
+                let instance = instances.entry(name).or_default();
+                *instance += 1;
+
+                let key = match *instance {
+                    1 => name.to_string(),
+                    n => format!("{name}#{n}"),
+                };
+
+                (key, state)
+            })
+            .collect()
+    }
 }
 
 impl<M1, M2, M3> From<(M1, M2, M3)> for ModuleGroups
@@ -168,7 +920,7 @@ where
 
 pub trait ModuleGetSet<M>
 where
-    M: TModule + 'static,
+    M: Module,
 {
     fn get(&self) -> impl Iterator<Item = &ModuleInfo<M>>;
     fn get_mut(&mut self) -> impl Iterator<Item = &mut ModuleInfo<M>>;
@@ -177,95 +929,26 @@ where
         self.get().count() > 0
     }
 
-    fn set_event(&mut self, event: impl Into<M::Event>) {
+    fn set_event(&mut self, event: impl Into<WidgetEvent<M>>) {
         let event = event.into();
         self.get_mut().for_each(|m| m.event = Some(event.clone()))
     }
 }
 
-macro_rules! modules {
-    ($($name:ident),+) => {
-        paste::paste! {
-            $(use [< $name:snake:lower >]::{$name, [< $name Event >]};)+
-
-            #[derive(Debug, From)]
-            pub enum Module {
-                $($name(ModuleInfo<$name>)),+
-            }
-
-            impl Module {
-                pub fn view(&self) -> Element<'_, AppMsg, Theme, Renderer> {
-                    match self {
-                        $(Module::$name(m) => m.view().map(|e| AppMsg::Module(e.into()))),+
-                    }
-                }
-
-                pub fn update(&mut self) -> Option<AppMsg> {
-                    match self {
-                        $(Module::$name(m) => m.update()),+
-                    }
-                }
-            }
-
-            #[derive(Debug, Clone, PartialEq, From)]
-            pub enum ModuleEvent {
-                $($name([< $name Event >])),+
-            }
-
-            #[derive(Display, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
-            pub enum ModuleConfig {
-                $($name(<$name as TModule>::Config)),+
-            }
-
-            impl From<ModuleConfig> for Module {
-                fn from(config: ModuleConfig) -> Self {
-                    match config {
-                        $(ModuleConfig::$name(c) => Self::$name(ModuleInfo::new_conf(c))),+
-                    }
-                }
-            }
-
-            $(
-                impl ModuleGetSet<$name> for Modules {
-                    fn get(&self) -> impl Iterator<Item = &ModuleInfo<$name>> {
-                        self.modules.iter().filter_map(|m| match m {
-                            Module::$name(m) => Some(m),
-                            _ => None
-                        })
-                    }
-
-                    fn get_mut(&mut self) -> impl Iterator<Item = &mut ModuleInfo<$name>> {
-                        self.modules.iter_mut().filter_map(|m| match m {
-                            Module::$name(m) => Some(m),
-                            _ => None
-                        })
-                    }
-                }
-
-                impl ModuleGetSet<$name> for ModuleGroups {
-                    fn get(&self) -> impl Iterator<Item = &ModuleInfo<$name>> {
-                        ModuleGetSet::<$name>::get(&self.left)
-                            .chain(ModuleGetSet::<$name>::get(&self.center))
-                            .chain(ModuleGetSet::<$name>::get(&self.right))
-                    }
-
-                    fn get_mut(&mut self) -> impl Iterator<Item = &mut ModuleInfo<$name>> {
-                        ModuleGetSet::<$name>::get_mut(&mut self.left)
-                            .chain(ModuleGetSet::<$name>::get_mut(&mut self.center))
-                            .chain(ModuleGetSet::<$name>::get_mut(&mut self.right))
-                    }
-                }
-            )+
-
-            impl ModuleGroups {
-                pub fn set_event(&mut self, event: impl Into<ModuleEvent>) {
-                    match event.into() {
-                        $(ModuleEvent::$name(e) => ModuleGetSet::<$name>::set_event(self, e)),+
-                    }
-                }
-            }
-        }
-    }
+/// Any of the modules, along with its widget
+#[derive(From, Modules)]
+pub enum AnyModule {
+    Clock(ModuleInfo<Clock>),
+    Battery(ModuleInfo<Battery>),
+    Audio(ModuleInfo<Audio>),
+    Microphone(ModuleInfo<Microphone>),
+    Privacy(ModuleInfo<Privacy>),
+    Custom(ModuleInfo<Custom>),
+    Button(ModuleInfo<Button>),
+    Tray(ModuleInfo<Tray>),
+    Backlight(ModuleInfo<Backlight>),
+    Cpu(ModuleInfo<Cpu>),
+    Memory(ModuleInfo<Memory>),
+    Disk(ModuleInfo<Disk>),
+    Temperature(ModuleInfo<Temperature>),
 }
-
-modules![Clock, Battery, Audio];
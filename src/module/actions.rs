@@ -0,0 +1,266 @@
+use std::time::{Duration, Instant};
+
+use iced::{
+    advanced::{
+        layout, mouse, overlay, renderer,
+        widget::{tree, Operation, Tree},
+        Clipboard, Layout, Shell, Widget,
+    },
+    event::{self, Event},
+    Element, Length, Rectangle, Size, Vector,
+};
+use serde::{Deserialize, Serialize};
+
+use super::audio::ScrollDirection;
+
+/// Longest time between the clicks of a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Mouse bindings of a module instance, set next to its config,
+/// e.g. `Clock(HH_MM, (on_middle_click: Some(Command("gnome-calendar"))))`.
+/// They take precedence over what the module itself does on the same input
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModuleActions {
+    pub on_click: Option<ModuleAction>,
+    pub on_middle_click: Option<ModuleAction>,
+    pub on_right_click: Option<ModuleAction>,
+    /// A double click replaces the second click, the first one still counts as a click
+    pub on_double_click: Option<ModuleAction>,
+    pub on_scroll_up: Option<ModuleAction>,
+    pub on_scroll_down: Option<ModuleAction>,
+}
+
+impl ModuleActions {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn get(&self, trigger: MouseTrigger) -> Option<&ModuleAction> {
+        match trigger {
+            MouseTrigger::Click => &self.on_click,
+            MouseTrigger::MiddleClick => &self.on_middle_click,
+            MouseTrigger::RightClick => &self.on_right_click,
+            MouseTrigger::DoubleClick => &self.on_double_click,
+            MouseTrigger::ScrollUp => &self.on_scroll_up,
+            MouseTrigger::ScrollDown => &self.on_scroll_down,
+        }
+        .as_ref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModuleAction {
+    /// Shell command to run in the background
+    Command(String),
+    /// Widget event of the module instance in the IPC JSON format,
+    /// e.g. `Event("\"SwitchFormat\"")` for the clock
+    Event(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseTrigger {
+    Click,
+    MiddleClick,
+    RightClick,
+    DoubleClick,
+    ScrollUp,
+    ScrollDown,
+}
+
+/// Wraps the view of a module, turning the bound mouse input into `on_trigger` messages
+/// before the module gets to see it
+pub fn bindings<'a, Message, Theme, Renderer>(
+    content: impl Into<Element<'a, Message, Theme, Renderer>>,
+    actions: &'a ModuleActions,
+    on_trigger: impl Fn(MouseTrigger) -> Message + 'a,
+) -> Element<'a, Message, Theme, Renderer>
+where
+    Message: 'a,
+    Theme: 'a,
+    Renderer: renderer::Renderer + 'a,
+{
+    match actions.is_empty() {
+        true => content.into(),
+        false => Element::new(Bindings {
+            content: content.into(),
+            actions,
+            on_trigger: Box::new(on_trigger),
+        }),
+    }
+}
+
+struct Bindings<'a, Message, Theme, Renderer> {
+    content: Element<'a, Message, Theme, Renderer>,
+    actions: &'a ModuleActions,
+    on_trigger: Box<dyn Fn(MouseTrigger) -> Message + 'a>,
+}
+
+#[derive(Default)]
+struct State {
+    last_click: Option<Instant>,
+}
+
+impl<Message, Theme, Renderer> Bindings<'_, Message, Theme, Renderer> {
+    /// The bound trigger of a mouse event, keeping track of the clicks for double clicks
+    fn trigger(&self, state: &mut State, event: &Event) -> Option<MouseTrigger> {
+        let trigger = match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let now = Instant::now();
+                let double = state
+                    .last_click
+                    .is_some_and(|last| now.duration_since(last) < DOUBLE_CLICK);
+                state.last_click = (!double).then_some(now);
+
+                match double && self.actions.on_double_click.is_some() {
+                    true => MouseTrigger::DoubleClick,
+                    false => MouseTrigger::Click,
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Middle)) => {
+                MouseTrigger::MiddleClick
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => {
+                MouseTrigger::RightClick
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                match ScrollDirection::from_delta(*delta) {
+                    ScrollDirection::Up => MouseTrigger::ScrollUp,
+                    ScrollDirection::Down => MouseTrigger::ScrollDown,
+                }
+            }
+            _ => return None,
+        };
+
+        self.actions.get(trigger).map(|_| trigger)
+    }
+}
+
+impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer>
+    for Bindings<'_, Message, Theme, Renderer>
+where
+    Renderer: renderer::Renderer,
+{
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        self.content
+            .as_widget()
+            .layout(&mut tree.children[0], renderer, limits)
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation,
+    ) {
+        self.content
+            .as_widget()
+            .operate(&mut tree.children[0], layout, renderer, operation);
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        if cursor.is_over(layout.bounds()) {
+            if let Some(trigger) = self.trigger(tree.state.downcast_mut(), &event) {
+                shell.publish((self.on_trigger)(trigger));
+                return event::Status::Captured;
+            }
+        }
+
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout,
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor,
+            viewport,
+        );
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        self.content
+            .as_widget_mut()
+            .overlay(&mut tree.children[0], layout, renderer, translation)
+    }
+}
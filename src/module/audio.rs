@@ -1,264 +1,954 @@
-use std::{ffi::CString, io::BufReader, os::unix::net::UnixStream, sync::Arc};
-
-use iced::widget::{rich_text, span};
+pub mod pipewire;
+pub mod pulse;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use derive_more::derive::Display;
+use iced::{
+    futures::{stream::BoxStream, StreamExt},
+    mouse::ScrollDelta,
+    widget::{
+        button, column, container, image, mouse_area, pick_list, rich_text, row, scrollable,
+        slider, span, svg, text,
+    },
+    Alignment, Element, Length, Renderer, Theme,
+};
 use iced_fonts::Nerd;
-use miette::IntoDiagnostic;
-use pulseaudio::protocol;
-use tokio::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use tokio::{
+    sync::{mpsc, watch},
+    task::AbortHandle,
+};
 
-use super::{NoConfig, TModule};
+use crate::util::find_icon;
+
+use super::{retry::RetryPolicy, Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput};
+
+/// Time to wait for more server events before refreshing, so bursts only cause one refresh
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(30);
+
+/// Connections to the sound server by the context of the modules sharing them
+static HUBS: Mutex<Vec<(ModuleContext, Weak<AudioHub>)>> = Mutex::new(Vec::new());
+
+/// Connection to the sound server, shared by every audio module with the same context.
+/// Closes once the last of them is gone
+struct AudioHub {
+    state: watch::Receiver<AudioHubState>,
+    task: AbortHandle,
+}
+
+#[derive(Debug, Clone, Default)]
+struct AudioHubState {
+    /// Where commands go, once the backend is up
+    commands: Option<mpsc::UnboundedSender<AudioCommand>>,
+    /// Latest state of the server, `None` until the first one comes in
+    output: Option<AudioOutput>,
+}
+
+impl AudioHub {
+    fn shared(context: ModuleContext) -> Arc<Self> {
+        let mut hubs = HUBS.lock().expect("Audio connections lock poisoned");
+        hubs.retain(|(_, hub)| hub.strong_count() > 0);
+
+        let running = hubs
+            .iter()
+            .filter(|(hub_context, _)| *hub_context == context)
+            .find_map(|(_, hub)| hub.upgrade());
+        if let Some(hub) = running {
+            return hub;
+        }
+
+        let hub = Arc::new(Self::connect(context));
+        hubs.push((context, Arc::downgrade(&hub)));
+        hub
+    }
+
+    fn connect(context: ModuleContext) -> Self {
+        let (state_tx, state) = watch::channel(AudioHubState::default());
+        let mut events = context.audio_backend.connect(context.retry);
+
+        let task =
+            tokio::spawn(async move {
+                while let Some(event) = events.next().await {
+                    match event {
+                        // Not news for the modules, they only need the channel once they send something
+                        AudioBackendEvent::Ready(commands) => {
+                            state_tx.send_if_modified(|state| {
+                                state.commands = Some(commands);
+                                false
+                            });
+                        }
+                        AudioBackendEvent::Data(info) => state_tx
+                            .send_modify(|state| state.output = Some(AudioOutput::Data(info))),
+                        AudioBackendEvent::Disconnected => state_tx
+                            .send_modify(|state| state.output = Some(AudioOutput::Disconnected)),
+                    }
+                }
+            });
+
+        Self {
+            state,
+            task: task.abort_handle(),
+        }
+    }
+}
+
+impl Drop for AudioHub {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A module's handle on the shared connection to the sound server
+pub struct AudioSource {
+    state: watch::Receiver<AudioHubState>,
+    _hub: Arc<AudioHub>,
+}
+
+impl AudioSource {
+    pub fn new(context: ModuleContext) -> Self {
+        let hub = AudioHub::shared(context);
+
+        Self {
+            state: hub.state.clone(),
+            _hub: hub,
+        }
+    }
+
+    /// Waits for the first state of the server, or for the news that it isn't there.
+    /// Modules joining a running connection get its latest state right away
+    pub async fn init(&mut self) -> miette::Result<AudioOutput> {
+        loop {
+            let output = self.state.borrow_and_update().output.clone();
+            if let Some(output) = output {
+                return Ok(output);
+            }
+
+            self.changed().await?;
+        }
+    }
+
+    /// Cancel safe, see [`Module::wait`]
+    pub async fn next(&mut self) -> miette::Result<AudioOutput> {
+        loop {
+            self.changed().await?;
+
+            let output = self.state.borrow_and_update().output.clone();
+            if let Some(output) = output {
+                return Ok(output);
+            }
+        }
+    }
+
+    async fn changed(&mut self) -> miette::Result<()> {
+        self.state
+            .changed()
+            .await
+            .map_err(|_| miette::miette!("The audio backend stopped"))
+    }
+
+    pub fn send(&self, command: AudioCommand) {
+        match &self.state.borrow().commands {
+            Some(commands) => {
+                if commands.send(command).is_err() {
+                    tracing::error!("Audio backend is gone, dropping the command");
+                }
+            }
+            None => tracing::warn!("Audio backend isn't ready yet, dropping {command:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AudioOutput {
+    Data(Arc<AudioInfo>),
+    Disconnected,
+}
 
-#[derive(Debug)]
 pub struct Audio {
-    default: Option<usize>,
-    data: Vec<AudioData>,
+    source: AudioSource,
+    /// Icon files of the streams by their icon name, looked up once per name
+    icons: Arc<HashMap<String, Option<PathBuf>>>,
+}
+
+impl Audio {
+    /// Looks up the icons of the streams that weren't seen before, off the UI thread
+    fn with_icons(&mut self, output: AudioOutput) -> AudioMixerOutput {
+        if let AudioOutput::Data(info) = &output {
+            for name in info.sink_inputs.iter().filter_map(|s| s.icon_name.as_ref()) {
+                if !self.icons.contains_key(name) {
+                    Arc::make_mut(&mut self.icons).insert(name.clone(), find_icon(name));
+                }
+            }
+        }
+
+        AudioMixerOutput {
+            audio: output,
+            icons: self.icons.clone(),
+        }
+    }
 }
 
-impl TModule for Audio {
-    type Config = NoConfig;
+impl Module for Audio {
+    type Config = AudioConfig;
     type Event = AudioEvent;
+    type Output = AudioMixerOutput;
+
+    type Widget = AudioWidget;
+
+    fn new(_config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        Ok(Self {
+            source: AudioSource::new(context),
+            icons: Arc::default(),
+        })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        let output = self.source.init().await?;
+        Ok(self.with_icons(output))
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        self.source.next().await.map(AudioEvent::Backend)
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            AudioEvent::Backend(output) => Ok(Some(self.with_icons(output))),
+            AudioEvent::Command(command) => {
+                self.source.send(command);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Server state along with the icons of the streams, for the mixer
+#[derive(Debug, Clone)]
+pub struct AudioMixerOutput {
+    audio: AudioOutput,
+    icons: Arc<HashMap<String, Option<PathBuf>>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum AudioEvent {
+    Backend(AudioOutput),
+    Command(AudioCommand),
+}
+
+#[derive(Debug)]
+pub struct AudioWidget {
+    config: AudioConfig,
+    devices: AudioDevices,
+
+    mixer_open: bool,
+    streams: Vec<AudioStreamData>,
+    /// Stream whose volume slider is being dragged, with the volume it's at.
+    /// The volume only gets set once the slider is released
+    dragging: Option<(u32, u8)>,
+}
+
+impl ModuleWidget<Audio> for AudioWidget {
+    type Event = AudioWidgetEvent;
+
+    fn new(config: AudioConfig) -> Self {
+        Self {
+            config,
+            devices: AudioDevices::new(AudioDeviceKind::Sink),
+
+            mixer_open: false,
+            streams: vec![],
+            dragging: None,
+        }
+    }
+
+    fn set(&mut self, output: AudioMixerOutput) {
+        match output.audio {
+            AudioOutput::Data(info) => {
+                self.devices.set(&info, |_| true);
+                self.streams = info
+                    .sink_inputs
+                    .iter()
+                    .map(|stream| AudioStreamData::new(stream, &info, &output.icons))
+                    .collect();
+
+                tracing::debug!("{self:#?}");
+            }
+            AudioOutput::Disconnected => {
+                self.devices.disconnect();
+                self.streams.clear();
+            }
+        }
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Audio>> {
+        let AudioConfig {
+            move_streams,
+            volume_step,
+            ..
+        } = &self.config;
+
+        match event {
+            AudioWidgetEvent::CycleDefault => self.devices.cycle_default(*move_streams),
+            AudioWidgetEvent::ToggleMute => self.devices.toggle_mute(),
+            AudioWidgetEvent::Scroll(direction) => {
+                self.devices.change_volume(direction, *volume_step)
+            }
+            AudioWidgetEvent::ToggleMixer => {
+                self.mixer_open = !self.mixer_open;
+                None
+            }
+            AudioWidgetEvent::StreamMute(index, mute) => {
+                Some(AudioCommand::SetStreamMute { index, mute })
+            }
+            AudioWidgetEvent::StreamVolume(index, volume) => {
+                Some(AudioCommand::SetStreamVolume { index, volume })
+            }
+            AudioWidgetEvent::DragStreamVolume(index, volume) => {
+                self.dragging = Some((index, volume));
+                None
+            }
+            AudioWidgetEvent::ReleaseStreamVolume => {
+                let (index, volume) = self.dragging.take()?;
+                Some(AudioCommand::SetStreamVolume { index, volume })
+            }
+            AudioWidgetEvent::MoveStream(index, sink) => {
+                Some(AudioCommand::MoveStream { index, sink })
+            }
+        }
+        .map(|command| ModuleWidgetUpdateOutput::module(AudioEvent::Command(command)))
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let devices = self
+            .devices
+            .view(&self.config.aliases, button::primary)
+            .map(|ev| match ev {
+                AudioDevicesEvent::CycleDefault => AudioWidgetEvent::CycleDefault,
+                AudioDevicesEvent::ToggleMute => AudioWidgetEvent::ToggleMute,
+                AudioDevicesEvent::Scroll(direction) => AudioWidgetEvent::Scroll(direction),
+            });
+
+        mouse_area(devices)
+            .on_middle_press(AudioWidgetEvent::ToggleMixer)
+            .into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        self.devices.tooltip()
+    }
+
+    fn has_popup(&self) -> bool {
+        self.mixer_open
+    }
+
+    fn close_popup(&mut self) {
+        self.mixer_open = false;
+    }
+
+    fn popup(&self) -> Option<Element<'_, Self::Event, Theme, Renderer>> {
+        if !self.mixer_open {
+            return None;
+        }
+
+        let sinks = self.devices.choices(&self.config.aliases);
+
+        let streams = self.streams.iter().map(|stream| {
+            let AudioStreamData {
+                index,
+                app_name,
+                icon,
+                sink,
+                muted,
+                volume,
+            } = stream;
+            let index = *index;
+            let volume = match self.dragging {
+                Some((dragged, volume)) if dragged == index => volume,
+                _ => *volume,
+            };
+
+            let icon: Element<'_, _, _, _> = match icon {
+                Some(path) if path.extension().is_some_and(|ext| ext == "svg") => {
+                    svg(svg::Handle::from_path(path))
+                        .width(24)
+                        .height(24)
+                        .into()
+                }
+                Some(path) => image(image::Handle::from_path(path))
+                    .width(24)
+                    .height(24)
+                    .into(),
+                None => text(iced_fonts::nerd::icon_to_string(Nerd::Application))
+                    .font(iced_fonts::NERD_FONT)
+                    .into(),
+            };
+            let mute_icon = match muted {
+                true => Nerd::VolumeMute,
+                false => Nerd::VolumeHigh,
+            };
+            let selected = sinks
+                .iter()
+                .find(|choice| Some(&choice.name) == sink.as_ref())
+                .cloned();
+
+            row![
+                icon,
+                text(app_name).width(Length::FillPortion(2)),
+                button(
+                    text(iced_fonts::nerd::icon_to_string(mute_icon)).font(iced_fonts::NERD_FONT)
+                )
+                .style(button::text)
+                .on_press(AudioWidgetEvent::StreamMute(index, !muted)),
+                slider(0..=100, volume, move |volume| {
+                    AudioWidgetEvent::DragStreamVolume(index, volume)
+                })
+                .on_release(AudioWidgetEvent::ReleaseStreamVolume)
+                .width(Length::FillPortion(3)),
+                text(format!("{volume}%")),
+                pick_list(sinks.clone(), selected, move |choice: AudioDeviceChoice| {
+                    AudioWidgetEvent::MoveStream(index, choice.name)
+                })
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center)
+            .into()
+        });
+
+        let content: Element<'_, _, _, _> = match self.streams.is_empty() {
+            true => text("No streams are playing").into(),
+            false => scrollable(column(streams).spacing(5)).into(),
+        };
+
+        Some(
+            container(content)
+                .padding(10)
+                .width(Length::Fill)
+                .style(container::rounded_box)
+                .into(),
+        )
+    }
+
+    fn state(&self) -> serde_json::Value {
+        let mut state = self.devices.state(&self.config.aliases);
+        state["streams"] = self
+            .streams
+            .iter()
+            .map(|stream| {
+                serde_json::json!({
+                    "index": stream.index,
+                    "app_name": stream.app_name,
+                    "sink": stream.sink,
+                    "muted": stream.muted,
+                    "volume": stream.volume,
+                })
+            })
+            .collect();
+
+        state
+    }
+}
 
-    fn new(_config: Self::Config) -> Self {
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Audio")]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Move the currently playing streams over to the new default device when switching
+    pub move_streams: bool,
+    /// Volume change (in percent) per scroll step
+    #[default = 5]
+    pub volume_step: u8,
+    /// Display names to show instead of the device descriptions,
+    /// keyed either by the device name (e.g. `alsa_output.pci-0000_00_1f.3.analog-stereo`)
+    /// or by its description
+    pub aliases: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum AudioWidgetEvent {
+    CycleDefault,
+    ToggleMute,
+    Scroll(ScrollDirection),
+    ToggleMixer,
+    StreamMute(u32, bool),
+    StreamVolume(u32, u8),
+    /// Moves the volume slider of a stream, without setting the volume yet
+    DragStreamVolume(u32, u8),
+    /// Sets the volume the dragged slider ended up at
+    ReleaseStreamVolume,
+    MoveStream(u32, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioCommand {
+    SetDefault {
+        kind: AudioDeviceKind,
+        name: String,
+        move_streams: bool,
+    },
+    SetMute {
+        kind: AudioDeviceKind,
+        name: String,
+        mute: bool,
+    },
+    SetVolume {
+        kind: AudioDeviceKind,
+        name: String,
+        volume: u8,
+    },
+    SetStreamMute {
+        index: u32,
+        mute: bool,
+    },
+    SetStreamVolume {
+        index: u32,
+        volume: u8,
+    },
+    MoveStream {
+        index: u32,
+        sink: String,
+    },
+}
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AudioDeviceKind {
+    Sink,
+    Source,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+}
+
+impl ScrollDirection {
+    pub fn from_delta(delta: ScrollDelta) -> Self {
+        let y = match delta {
+            ScrollDelta::Lines { y, .. } | ScrollDelta::Pixels { y, .. } => y,
+        };
+
+        match y >= 0.0 {
+            true => Self::Up,
+            false => Self::Down,
+        }
+    }
+}
+
+/// Display state shared by the modules showing the default device of some kind
+#[derive(Debug)]
+pub struct AudioDevices {
+    kind: AudioDeviceKind,
+    connected: bool,
+    default: Option<usize>,
+    data: Vec<AudioData>,
+}
+
+impl AudioDevices {
+    pub fn new(kind: AudioDeviceKind) -> Self {
         Self {
+            kind,
+            connected: false,
             default: None,
             data: vec![],
         }
     }
 
-    fn update(&mut self, event: Self::Event) -> Option<crate::app::AppMsg> {
-        match event {
-            AudioEvent::SetData(info) => {
-                let info = info.blocking_lock();
+    pub fn set(&mut self, info: &AudioInfo, filter: impl Fn(&AudioDevice) -> bool) {
+        let devices = info.devices(self.kind).iter().filter(|d| filter(d));
+        let default_device = info.server_info.default_device(self.kind);
 
-                self.default = info.default_device_index();
-                self.data = info.devices.iter().map(AudioData::new).collect();
+        self.connected = true;
+        self.data = devices.map(AudioData::new).collect();
+        self.default = self.data.iter().position(|d| d.name == default_device);
+    }
 
-                tracing::debug!("{self:#?}");
-            }
+    pub fn disconnect(&mut self) {
+        self.connected = false;
+        self.default = None;
+        self.data.clear();
+    }
+
+    pub fn default_data(&self) -> Option<&AudioData> {
+        self.default.and_then(|i| self.data.get(i))
+    }
+
+    pub fn cycle_default(&self, move_streams: bool) -> Option<AudioCommand> {
+        if self.data.len() < 2 {
+            return None;
         }
 
-        None
+        let next = self.default.map(|i| (i + 1) % self.data.len()).unwrap_or(0);
+
+        Some(AudioCommand::SetDefault {
+            kind: self.kind,
+            name: self.data[next].name.clone(),
+            move_streams,
+        })
     }
 
-    fn view(&self) -> iced::Element<'_, Self::Event, iced::Theme, iced::Renderer> {
-        let default_device = match self.default.and_then(|i| self.data.get(i)) {
-            Some(data) => data,
-            None => &AudioData::unknown(),
+    pub fn toggle_mute(&self) -> Option<AudioCommand> {
+        self.default_data().map(|data| AudioCommand::SetMute {
+            kind: self.kind,
+            name: data.name.clone(),
+            mute: !data.muted,
+        })
+    }
+
+    pub fn change_volume(&self, direction: ScrollDirection, step: u8) -> Option<AudioCommand> {
+        self.default_data().map(|data| AudioCommand::SetVolume {
+            kind: self.kind,
+            name: data.name.clone(),
+            volume: match direction {
+                ScrollDirection::Up => data.volume.saturating_add(step).min(100),
+                ScrollDirection::Down => data.volume.saturating_sub(step),
+            },
+        })
+    }
+
+    pub fn choices(&self, aliases: &BTreeMap<String, String>) -> Vec<AudioDeviceChoice> {
+        self.data
+            .iter()
+            .map(|data| AudioDeviceChoice {
+                name: data.name.clone(),
+                label: data.display_name(aliases).to_string(),
+            })
+            .collect()
+    }
+
+    pub fn state(&self, aliases: &BTreeMap<String, String>) -> serde_json::Value {
+        let default = self.default_data().map(|data| {
+            serde_json::json!({
+                "name": data.name,
+                "display_name": data.display_name(aliases),
+                "muted": data.muted,
+                "volume": data.volume,
+            })
+        });
+
+        serde_json::json!({
+            "connected": self.connected,
+            "default": default,
+            "devices": self.data.iter().map(|data| &data.name).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Full description of the default device, which the label may shorten to an alias
+    pub fn tooltip(&self) -> Option<String> {
+        if !self.connected {
+            return Some("Not connected to the sound server".into());
+        }
+
+        let device = self.default_data()?;
+        let muted = match device.muted {
+            true => ", muted",
+            false => "",
+        };
+
+        Some(format!(
+            "{}\n{}% volume{muted}",
+            device.description, device.volume
+        ))
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        aliases: &'a BTreeMap<String, String>,
+        style: impl Fn(&Theme, button::Status) -> button::Style + 'a,
+    ) -> Element<'a, AudioDevicesEvent, Theme, Renderer> {
+        let unknown = AudioData::unknown(self.kind);
+        let device = self.default_data().unwrap_or(&unknown);
+
+        let label = match self.connected {
+            true => format!(" {}% {}", device.volume, device.display_name(aliases)),
+            false => " disconnected".into(),
         };
 
-        rich_text![
-            span(iced_fonts::nerd::icon_to_string(default_device.icon)).font(iced_fonts::NERD_FONT),
-            span(format!(" {}%", default_device.volume))
-        ]
+        mouse_area(
+            button(rich_text![
+                span(iced_fonts::nerd::icon_to_string(device.icon)).font(iced_fonts::NERD_FONT),
+                span(label)
+            ])
+            .style(style)
+            .on_press(AudioDevicesEvent::CycleDefault),
+        )
+        .on_right_press(AudioDevicesEvent::ToggleMute)
+        .on_scroll(|delta| AudioDevicesEvent::Scroll(ScrollDirection::from_delta(delta)))
         .into()
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum AudioEvent {
-    SetData(Arc<Mutex<AudioInfo>>),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioDevicesEvent {
+    CycleDefault,
+    ToggleMute,
+    Scroll(ScrollDirection),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[display("{label}")]
+pub struct AudioDeviceChoice {
+    name: String,
+    label: String,
+}
+
+#[derive(Debug)]
+pub struct AudioStreamData {
+    index: u32,
+    app_name: String,
+    icon: Option<PathBuf>,
+    sink: Option<String>,
+
+    muted: bool,
+    volume: u8,
 }
 
-impl PartialEq for AudioEvent {
-    fn eq(&self, _other: &Self) -> bool {
-        true
+impl AudioStreamData {
+    fn new(
+        stream: &AudioStream,
+        info: &AudioInfo,
+        icons: &HashMap<String, Option<PathBuf>>,
+    ) -> Self {
+        Self {
+            index: stream.index,
+            app_name: stream.app_name.clone(),
+            icon: stream
+                .icon_name
+                .as_ref()
+                .and_then(|name| icons.get(name)?.clone()),
+            sink: info
+                .sinks
+                .iter()
+                .find(|d| d.index == stream.device_index)
+                .map(|d| d.name.clone()),
+
+            muted: stream.muted,
+            volume: stream.volume,
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct AudioData {
+    name: String,
+    description: String,
+
     icon: Nerd,
+    muted: bool,
     volume: u8,
 }
 
 impl AudioData {
     fn new(device_info: &AudioDevice) -> Self {
         Self {
+            name: device_info.name.clone(),
+            description: device_info.description.clone(),
+
             icon: device_info.icon(),
+            muted: device_info.muted,
             volume: device_info.volume,
         }
     }
 
-    fn unknown() -> Self {
+    fn unknown(kind: AudioDeviceKind) -> Self {
         Self {
-            icon: Nerd::VolumeOff,
+            name: String::new(),
+            description: "Unknown".into(),
+
+            icon: match kind {
+                AudioDeviceKind::Sink => Nerd::VolumeOff,
+                AudioDeviceKind::Source => Nerd::MicrophoneOff,
+            },
+            muted: true,
             volume: 0,
         }
     }
-}
-
-#[derive(Debug)]
-pub struct AudioInfo {
-    sock: BufReader<UnixStream>,
-    server_info: AudioServerInfo,
-    devices: Vec<AudioDevice>,
-}
 
-impl AudioInfo {
-    pub async fn init() -> miette::Result<Self> {
-        let socket_path = pulseaudio::socket_path_from_env()
-            .ok_or_else(|| miette::miette!("PulseAudio is not available"))?;
-        let mut sock = BufReader::new(UnixStream::connect(socket_path).into_diagnostic()?);
-
-        let cookie = tokio::fs::read(
-            pulseaudio::cookie_path_from_env()
-                .ok_or_else(|| miette::miette!("Failed to get cookie path"))?,
-        )
-        .await
-        .into_diagnostic()?;
-        let auth = protocol::AuthParams {
-            version: protocol::MAX_VERSION,
-            supports_shm: false,
-            supports_memfd: false,
-            cookie,
-        };
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
 
-        protocol::write_command_message(
-            sock.get_mut(),
-            0,
-            protocol::Command::Auth(auth),
-            protocol::MAX_VERSION,
-        )
-        .into_diagnostic()?;
-        let (_, auth_info) =
-            protocol::read_reply_message::<protocol::AuthReply>(&mut sock, protocol::MAX_VERSION)
-                .into_diagnostic()?;
-        let protocol_version = std::cmp::min(protocol::MAX_VERSION, auth_info.version);
-
-        let mut props = protocol::Props::new();
-        props.set(
-            protocol::Prop::ApplicationName,
-            CString::new("rbar").into_diagnostic()?,
-        );
-        protocol::write_command_message(
-            sock.get_mut(),
-            1,
-            protocol::Command::SetClientName(props),
-            protocol_version,
-        )
-        .into_diagnostic()?;
+    fn display_name<'a>(&'a self, aliases: &'a BTreeMap<String, String>) -> &'a str {
+        aliases
+            .get(&self.name)
+            .or_else(|| aliases.get(&self.description))
+            .unwrap_or(&self.description)
+    }
+}
 
-        let _ = protocol::read_reply_message::<protocol::SetClientNameReply>(
-            &mut sock,
-            protocol_version,
-        )
-        .into_diagnostic()?;
+/// Sound server the audio modules talk to
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AudioBackend {
+    /// PulseAudio (or pipewire-pulse) when its socket is around, native PipeWire otherwise
+    #[default]
+    Auto,
+    PulseAudio,
+    PipeWire,
+}
 
-        protocol::write_command_message(
-            sock.get_mut(),
-            2,
-            protocol::Command::GetServerInfo,
-            protocol_version,
-        )
-        .into_diagnostic()?;
+impl AudioBackend {
+    /// Picks the actual backend for [`AudioBackend::Auto`]
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Auto => match pulseaudio::socket_path_from_env() {
+                Some(_) => Self::PulseAudio,
+                None => Self::PipeWire,
+            },
+            backend => backend,
+        }
+    }
 
-        let (_, server_info) = protocol::read_reply_message::<protocol::command::ServerInfo>(
-            &mut sock,
-            protocol_version,
-        )
-        .into_diagnostic()?;
-        let server_info = AudioServerInfo::new(server_info)?;
-
-        protocol::write_command_message(
-            sock.get_mut(),
-            3,
-            protocol::Command::GetSinkInfoList,
-            protocol_version,
-        )
-        .into_diagnostic()?;
+    /// Keeps reconnecting with the delays of the policy, never giving up
+    pub fn connect(self, retry: RetryPolicy) -> BoxStream<'static, AudioBackendEvent> {
+        match self.resolve() {
+            Self::Auto | Self::PulseAudio => pulse::connect(retry).boxed(),
+            Self::PipeWire => pipewire::connect(retry).boxed(),
+        }
+    }
+}
 
-        let (_, sinks) =
-            protocol::read_reply_message::<protocol::SinkInfoList>(&mut sock, protocol_version)
-                .into_diagnostic()?;
+#[derive(Debug, Clone)]
+pub enum AudioBackendEvent {
+    /// The backend is up and listens for commands on this channel
+    Ready(mpsc::UnboundedSender<AudioCommand>),
+    Data(Arc<AudioInfo>),
+    Disconnected,
+}
 
-        let devices = sinks
-            .into_iter()
-            .map(AudioDevice::new)
-            .collect::<miette::Result<Vec<_>>>()?;
+/// Snapshot of the audio server state
+#[derive(Debug, Default)]
+pub struct AudioInfo {
+    server_info: AudioServerInfo,
+    sinks: Vec<AudioDevice>,
+    sources: Vec<AudioDevice>,
+    sink_inputs: Vec<AudioStream>,
+    source_outputs: Vec<AudioStream>,
+    /// Only available when the backend can see the video graph, i.e. PipeWire
+    video_streams: Option<Vec<VideoStream>>,
+}
 
-        Ok(Self {
-            sock,
-            server_info,
-            devices,
-        })
+impl AudioInfo {
+    pub fn devices(&self, kind: AudioDeviceKind) -> &[AudioDevice] {
+        match kind {
+            AudioDeviceKind::Sink => &self.sinks,
+            AudioDeviceKind::Source => &self.sources,
+        }
     }
 
-    fn default_device_index(&self) -> Option<usize> {
-        self.devices
+    /// Names of the apps currently recording from a source,
+    /// optionally leaving out the ones that only listen to a sink monitor (peak meters, desktop audio)
+    pub fn recording_apps(&self, ignore_monitors: bool) -> impl Iterator<Item = &str> {
+        self.source_outputs
             .iter()
-            .position(|d| d.name == self.server_info.default_device)
+            .filter(move |stream| {
+                !ignore_monitors
+                    || self
+                        .sources
+                        .iter()
+                        .any(|source| source.index == stream.device_index && !source.is_monitor())
+            })
+            .map(|stream| stream.app_name.as_str())
+    }
+
+    pub fn video_streams(&self) -> Option<&[VideoStream]> {
+        self.video_streams.as_deref()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct AudioServerInfo {
-    server: protocol::ServerInfo,
-    default_device: String,
+    default_sink: String,
+    default_source: String,
 }
 
 impl AudioServerInfo {
-    fn new(server: protocol::ServerInfo) -> miette::Result<Self> {
-        let default_device = server
-            .default_sink_name
-            .as_ref()
-            .ok_or_else(|| miette::miette!("Failed to get default audio device"))?
-            .to_str()
-            .into_diagnostic()?
-            .to_string();
-
-        Ok(Self {
-            server,
-            default_device,
-        })
+    fn default_device(&self, kind: AudioDeviceKind) -> &str {
+        match kind {
+            AudioDeviceKind::Sink => &self.default_sink,
+            AudioDeviceKind::Source => &self.default_source,
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct AudioDevice {
-    sink: protocol::SinkInfo,
+    kind: AudioDeviceKind,
+    index: u32,
+    channels: u8,
 
     name: String,
     description: String,
+    monitor: bool,
 
     muted: bool,
     volume: u8,
 }
 
 impl AudioDevice {
-    fn new(sink: protocol::SinkInfo) -> miette::Result<Self> {
-        let name = sink.name.to_str().into_diagnostic()?.to_string();
-        let description = match &sink.description {
-            Some(str) => Some(str.to_str().into_diagnostic()?.to_string()),
-            None => None,
-        }
-        .unwrap_or_else(|| "Unknown".into());
+    pub fn is_monitor(&self) -> bool {
+        self.monitor
+    }
 
-        let muted = sink.muted;
-        let volume = (sink.base_volume.to_linear() * 100.0).round() as u8;
+    fn icon(&self) -> Nerd {
+        match self.kind {
+            AudioDeviceKind::Sink => {
+                if self.muted {
+                    return Nerd::VolumeMute;
+                }
+
+                match self.volume {
+                    0 => Nerd::VolumeOff,
+                    1..=33 => Nerd::VolumeLow,
+                    34..=66 => Nerd::VolumeMedium,
+                    _ => Nerd::VolumeHigh,
+                }
+            }
+            AudioDeviceKind::Source => match self.muted {
+                true => Nerd::MicrophoneOff,
+                false => Nerd::Microphone,
+            },
+        }
+    }
+}
 
-        tracing::debug!("{name} ({description}) [{muted} {volume}]");
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VideoCaptureKind {
+    Camera,
+    Screen,
+}
 
-        Ok(Self {
-            sink,
+/// App consuming a video source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoStream {
+    kind: VideoCaptureKind,
+    app_name: String,
+}
 
-            name,
-            description,
+impl VideoStream {
+    pub fn kind(&self) -> VideoCaptureKind {
+        self.kind
+    }
 
-            muted,
-            volume,
-        })
+    pub fn app_name(&self) -> &str {
+        &self.app_name
     }
+}
 
-    fn icon(&self) -> Nerd {
-        if self.muted {
-            return Nerd::VolumeMute;
-        }
+#[derive(Debug)]
+pub struct AudioStream {
+    index: u32,
+    device_index: u32,
+    channels: u8,
 
-        match self.volume {
-            0 => Nerd::VolumeOff,
-            1..=33 => Nerd::VolumeLow,
-            34..=66 => Nerd::VolumeMedium,
-            _ => Nerd::VolumeHigh,
-        }
-    }
+    app_name: String,
+    icon_name: Option<String>,
+
+    muted: bool,
+    volume: u8,
 }
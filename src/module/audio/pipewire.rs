@@ -0,0 +1,895 @@
+use std::{cell::RefCell, collections::HashMap, io::Cursor, path::Path, rc::Rc, sync::Arc};
+
+use ::pipewire as pw;
+use iced::futures::{channel::mpsc::Sender, SinkExt, Stream};
+use miette::IntoDiagnostic;
+use pw::{
+    context::Context,
+    device::{Device, DeviceListener},
+    main_loop::MainLoop,
+    metadata::{Metadata, MetadataListener},
+    node::{Node, NodeListener},
+    registry::{GlobalObject, Registry},
+    spa::{
+        self,
+        param::ParamType,
+        pod::{
+            deserialize::PodDeserializer, serialize::PodSerializer, Object, Pod, Property, Value,
+            ValueArray,
+        },
+        utils::dict::DictRef,
+    },
+    types::ObjectType,
+};
+use tokio::sync::mpsc;
+
+use crate::module::retry::{Backoff, RetryPolicy};
+
+use super::{
+    AudioBackendEvent, AudioCommand, AudioDevice, AudioDeviceKind, AudioInfo, AudioServerInfo,
+    AudioStream, VideoCaptureKind, VideoStream, EVENT_DEBOUNCE,
+};
+
+/// Runs the PipeWire main loop on its own thread, restarting it with backoff when it dies,
+/// and streams the graph state to the app
+pub fn connect(retry: RetryPolicy) -> impl Stream<Item = AudioBackendEvent> {
+    iced::stream::channel(16, move |mut output| async move {
+        let (commands_tx, mut commands) = mpsc::unbounded_channel();
+        if output
+            .send(AudioBackendEvent::Ready(commands_tx))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut backoff = Backoff::new(retry);
+
+        loop {
+            match start().await {
+                Ok((loop_tx, mut infos, info)) => {
+                    tracing::info!("Connected to PipeWire");
+                    backoff.reset();
+
+                    if output
+                        .send(AudioBackendEvent::Data(info.into()))
+                        .await
+                        .is_err()
+                    {
+                        let _ = loop_tx.send(LoopMessage::Quit);
+                        return;
+                    }
+
+                    match serve(&loop_tx, &mut infos, &mut commands, &mut output).await {
+                        Ok(()) => {
+                            let _ = loop_tx.send(LoopMessage::Quit);
+                            return;
+                        }
+                        Err(err) => tracing::warn!("Lost connection to PipeWire: {err}"),
+                    }
+                }
+                Err(err) => tracing::warn!("Failed to connect to PipeWire: {err}"),
+            }
+
+            if output.send(AudioBackendEvent::Disconnected).await.is_err() {
+                return;
+            }
+
+            let delay = backoff.fail_forever();
+            tracing::debug!(
+                "Reconnecting to PipeWire in {delay:?}, {} failure(s) in a row",
+                backoff.failures()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    })
+}
+
+/// Whether a PipeWire server is listening, looking for its socket where libpipewire does
+pub fn running() -> bool {
+    let remote = std::env::var_os("PIPEWIRE_REMOTE").unwrap_or_else(|| "pipewire-0".into());
+    let remote = Path::new(&remote);
+    if remote.is_absolute() {
+        return remote.exists();
+    }
+
+    ["PIPEWIRE_RUNTIME_DIR", "XDG_RUNTIME_DIR"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .any(|dir| Path::new(&dir).join(remote).exists())
+}
+
+/// Watches only the video streams, for when the audio goes through PulseAudio
+/// but the camera and screen casts still need to be tracked
+pub fn video(retry: RetryPolicy) -> impl Stream<Item = Arc<Vec<VideoStream>>> {
+    iced::stream::channel(16, move |mut output| async move {
+        let mut backoff = Backoff::new(retry);
+
+        loop {
+            match start().await {
+                Ok((loop_tx, mut infos, mut info)) => {
+                    tracing::info!("Watching PipeWire video streams");
+                    backoff.reset();
+
+                    let mut last = None;
+                    loop {
+                        let video = info.video_streams.unwrap_or_default();
+                        if last.as_ref() != Some(&video) {
+                            if output.send(Arc::new(video.clone())).await.is_err() {
+                                let _ = loop_tx.send(LoopMessage::Quit);
+                                return;
+                            }
+
+                            last = Some(video);
+                        }
+
+                        match infos.recv().await {
+                            Some(newer) => info = newer,
+                            None => break,
+                        }
+                    }
+
+                    tracing::warn!("Lost connection to PipeWire");
+                }
+                Err(err) => tracing::warn!("Failed to connect to PipeWire: {err}"),
+            }
+
+            if output.send(Default::default()).await.is_err() {
+                return;
+            }
+
+            let delay = backoff.fail_forever();
+            tracing::debug!(
+                "Reconnecting to PipeWire in {delay:?}, {} failure(s) in a row",
+                backoff.failures()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    })
+}
+
+/// Spawns the main loop thread, resolving once it's connected and published the first snapshot
+async fn start() -> miette::Result<(
+    pw::channel::Sender<LoopMessage>,
+    mpsc::UnboundedReceiver<AudioInfo>,
+    AudioInfo,
+)> {
+    let (loop_tx, loop_rx) = pw::channel::channel();
+    let (info_tx, mut infos) = mpsc::unbounded_channel();
+
+    std::thread::Builder::new()
+        .name("rbar-pipewire".into())
+        .spawn(move || {
+            if let Err(err) = run(loop_rx, info_tx) {
+                tracing::warn!("PipeWire main loop failed: {err}");
+            }
+        })
+        .into_diagnostic()?;
+
+    let info = infos
+        .recv()
+        .await
+        .ok_or_else(|| miette::miette!("PipeWire main loop stopped before connecting"))?;
+
+    Ok((loop_tx, infos, info))
+}
+
+/// Forwards commands to the main loop and state updates to the app
+/// until the loop dies (`Err`) or the app goes away (`Ok`)
+async fn serve(
+    loop_tx: &pw::channel::Sender<LoopMessage>,
+    infos: &mut mpsc::UnboundedReceiver<AudioInfo>,
+    commands: &mut mpsc::UnboundedReceiver<AudioCommand>,
+    output: &mut Sender<AudioBackendEvent>,
+) -> miette::Result<()> {
+    loop {
+        tokio::select! {
+            command = commands.recv() => {
+                let Some(command) = command else {
+                    return Ok(());
+                };
+
+                loop_tx
+                    .send(LoopMessage::Command(command))
+                    .map_err(|_| miette::miette!("PipeWire main loop is gone"))?;
+            }
+            info = infos.recv() => {
+                let Some(mut info) = info else {
+                    return Err(miette::miette!("PipeWire main loop stopped"));
+                };
+
+                tokio::time::sleep(EVENT_DEBOUNCE).await;
+                while let Ok(newer) = infos.try_recv() {
+                    info = newer;
+                }
+
+                if output.send(AudioBackendEvent::Data(info.into())).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+enum LoopMessage {
+    Command(AudioCommand),
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Sink,
+    Source,
+    /// Playback stream, the PipeWire equivalent of a sink input
+    Playback,
+    /// Capture stream, the PipeWire equivalent of a source output
+    Capture,
+    /// Camera or screen cast node producing video
+    VideoSource,
+    /// Stream consuming a video source
+    VideoCapture,
+}
+
+impl NodeKind {
+    fn from_media_class(class: &str) -> Option<Self> {
+        match class {
+            "Audio/Sink" | "Audio/Duplex" => Some(Self::Sink),
+            "Audio/Source" | "Audio/Source/Virtual" => Some(Self::Source),
+            "Stream/Output/Audio" => Some(Self::Playback),
+            "Stream/Input/Audio" => Some(Self::Capture),
+            "Video/Source" | "Stream/Output/Video" => Some(Self::VideoSource),
+            "Stream/Input/Video" => Some(Self::VideoCapture),
+            _ => None,
+        }
+    }
+}
+
+struct PwNode {
+    proxy: Node,
+    _listener: NodeListener,
+
+    kind: NodeKind,
+    name: String,
+    description: String,
+    app_name: Option<String>,
+    icon_name: Option<String>,
+    /// Video source backed by an actual camera, anything else is considered a screen cast
+    camera: bool,
+    /// Device object and route device (`card.profile.device`) of an ALSA sink or source,
+    /// whose volume lives on the device route rather than on the node
+    route: Option<(u32, i32)>,
+
+    channels: u8,
+    volume: u8,
+    muted: bool,
+}
+
+impl PwNode {
+    fn app_name(&self) -> String {
+        self.app_name.clone().unwrap_or_else(|| self.name.clone())
+    }
+}
+
+/// Card of ALSA devices, owning the routes (ports) their volumes get set on
+struct PwDevice {
+    proxy: Device,
+    _listener: DeviceListener,
+
+    /// Index of the active route by the route device it belongs to
+    routes: HashMap<i32, i32>,
+}
+
+#[derive(Default)]
+struct State {
+    nodes: HashMap<u32, PwNode>,
+    devices: HashMap<u32, PwDevice>,
+    /// Link id to its (output node, input node)
+    links: HashMap<u32, (u32, u32)>,
+    metadata: Option<(Metadata, MetadataListener)>,
+
+    default_sink: String,
+    default_source: String,
+}
+
+impl State {
+    fn info(&self) -> AudioInfo {
+        let device = |(id, node): (&u32, &PwNode), kind| AudioDevice {
+            kind,
+            index: *id,
+            channels: node.channels,
+
+            name: node.name.clone(),
+            description: node.description.clone(),
+            monitor: false,
+
+            muted: node.muted,
+            volume: node.volume,
+        };
+        let devices = |kind, device_kind| {
+            self.nodes
+                .iter()
+                .filter(|(_, node)| node.kind == kind)
+                .map(|node| device(node, device_kind))
+                .collect()
+        };
+
+        let streams = |kind| {
+            self.nodes
+                .iter()
+                .filter(|(_, node)| node.kind == kind)
+                .map(|(id, node)| AudioStream {
+                    index: *id,
+                    device_index: self.linked_node(*id, kind).unwrap_or(u32::MAX),
+                    channels: node.channels,
+
+                    app_name: node.app_name(),
+                    icon_name: node.icon_name.clone(),
+
+                    muted: node.muted,
+                    volume: node.volume,
+                })
+                .collect()
+        };
+
+        let video_streams = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.kind == NodeKind::VideoCapture)
+            .filter_map(|(id, node)| {
+                let source = self.nodes.get(&self.linked_node(*id, node.kind)?)?;
+
+                Some(VideoStream {
+                    kind: match source.camera {
+                        true => VideoCaptureKind::Camera,
+                        false => VideoCaptureKind::Screen,
+                    },
+                    app_name: node.app_name(),
+                })
+            })
+            .collect();
+
+        AudioInfo {
+            server_info: AudioServerInfo {
+                default_sink: self.default_sink.clone(),
+                default_source: self.default_source.clone(),
+            },
+            sinks: devices(NodeKind::Sink, AudioDeviceKind::Sink),
+            sources: devices(NodeKind::Source, AudioDeviceKind::Source),
+            sink_inputs: streams(NodeKind::Playback),
+            source_outputs: streams(NodeKind::Capture),
+            video_streams: Some(video_streams),
+        }
+    }
+
+    /// Device on the other end of a stream: playback streams link into their sink,
+    /// capture streams get linked from their source
+    fn linked_node(&self, stream: u32, kind: NodeKind) -> Option<u32> {
+        self.links.values().find_map(|&(output, input)| match kind {
+            NodeKind::Playback if output == stream => Some(input),
+            NodeKind::Capture | NodeKind::VideoCapture if input == stream => Some(output),
+            _ => None,
+        })
+    }
+
+    fn node_by_name(&self, name: &str) -> Option<&PwNode> {
+        self.nodes.values().find(|node| node.name == name)
+    }
+
+    fn exec(&self, command: AudioCommand) -> miette::Result<()> {
+        match command {
+            AudioCommand::SetDefault {
+                kind,
+                name,
+                move_streams,
+            } => {
+                tracing::debug!("Setting default {kind} to {name}");
+
+                let (key, stream_kind) = match kind {
+                    AudioDeviceKind::Sink => ("default.configured.audio.sink", NodeKind::Playback),
+                    AudioDeviceKind::Source => {
+                        ("default.configured.audio.source", NodeKind::Capture)
+                    }
+                };
+
+                let metadata = self.metadata()?;
+                let value = serde_json::json!({ "name": name }).to_string();
+                metadata.set_property(0, key, Some("Spa:String:JSON"), Some(&value));
+
+                if move_streams {
+                    self.nodes
+                        .iter()
+                        .filter(|(_, node)| node.kind == stream_kind)
+                        .for_each(|(id, _)| {
+                            metadata.set_property(
+                                *id,
+                                "target.object",
+                                Some("Spa:String"),
+                                Some(&name),
+                            )
+                        });
+                }
+            }
+            AudioCommand::SetMute { kind, name, mute } => {
+                tracing::debug!("Setting {kind} {name} mute to {mute}");
+
+                let node = self
+                    .node_by_name(&name)
+                    .ok_or_else(|| miette::miette!("Unknown audio device {name}"))?;
+                self.set_device_props(node, mute_props(mute))?;
+            }
+            AudioCommand::SetVolume { kind, name, volume } => {
+                tracing::debug!("Setting {kind} {name} volume to {volume}%");
+
+                let node = self
+                    .node_by_name(&name)
+                    .ok_or_else(|| miette::miette!("Unknown audio device {name}"))?;
+                self.set_device_props(node, volume_props(node.channels, volume))?;
+            }
+            AudioCommand::SetStreamMute { index, mute } => {
+                tracing::debug!("Setting stream {index} mute to {mute}");
+
+                let node = self
+                    .nodes
+                    .get(&index)
+                    .ok_or_else(|| miette::miette!("Unknown audio stream {index}"))?;
+                set_props(&node.proxy, mute_props(mute))?;
+            }
+            AudioCommand::SetStreamVolume { index, volume } => {
+                tracing::debug!("Setting stream {index} volume to {volume}%");
+
+                let node = self
+                    .nodes
+                    .get(&index)
+                    .ok_or_else(|| miette::miette!("Unknown audio stream {index}"))?;
+                set_props(&node.proxy, volume_props(node.channels, volume))?;
+            }
+            AudioCommand::MoveStream { index, sink } => {
+                tracing::debug!("Moving stream {index} to {sink}");
+
+                self.metadata()?.set_property(
+                    index,
+                    "target.object",
+                    Some("Spa:String"),
+                    Some(&sink),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the props on the active route of the device behind the node, like `wpctl` does,
+    /// so the session manager keeps them for the port. Nodes without a route get them directly
+    fn set_device_props(&self, node: &PwNode, properties: Vec<Property>) -> miette::Result<()> {
+        let route = node.route.and_then(|(device_id, route_device)| {
+            let device = self.devices.get(&device_id)?;
+            let index = *device.routes.get(&route_device)?;
+            Some((device, index, route_device))
+        });
+
+        let Some((device, index, route_device)) = route else {
+            return set_props(&node.proxy, properties);
+        };
+
+        let route = Object {
+            type_: spa::sys::SPA_TYPE_OBJECT_ParamRoute,
+            id: spa::sys::SPA_PARAM_Route,
+            properties: vec![
+                Property::new(spa::sys::SPA_PARAM_ROUTE_index, Value::Int(index)),
+                Property::new(spa::sys::SPA_PARAM_ROUTE_device, Value::Int(route_device)),
+                Property::new(
+                    spa::sys::SPA_PARAM_ROUTE_props,
+                    Value::Object(Object {
+                        type_: spa::sys::SPA_TYPE_OBJECT_Props,
+                        id: spa::sys::SPA_PARAM_Route,
+                        properties,
+                    }),
+                ),
+                Property::new(spa::sys::SPA_PARAM_ROUTE_save, Value::Bool(true)),
+            ],
+        };
+
+        with_pod(route, |pod| {
+            device.proxy.set_param(ParamType::Route, 0, pod)
+        })
+    }
+
+    fn metadata(&self) -> miette::Result<&Metadata> {
+        self.metadata
+            .as_ref()
+            .map(|(metadata, _)| metadata)
+            .ok_or_else(|| miette::miette!("PipeWire default metadata isn't available (yet)"))
+    }
+}
+
+fn run(
+    messages: pw::channel::Receiver<LoopMessage>,
+    infos: mpsc::UnboundedSender<AudioInfo>,
+) -> miette::Result<()> {
+    pw::init();
+
+    let main_loop = MainLoop::new(None).into_diagnostic()?;
+    let context = Context::new(&main_loop).into_diagnostic()?;
+    let core = context.connect(None).into_diagnostic()?;
+    let registry = Rc::new(core.get_registry().into_diagnostic()?);
+
+    let state = Rc::new(RefCell::new(State::default()));
+    let publish = Rc::new({
+        let state = Rc::downgrade(&state);
+        let main_loop = main_loop.downgrade();
+
+        move || {
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+
+            if infos.send(state.borrow().info()).is_err() {
+                if let Some(main_loop) = main_loop.upgrade() {
+                    main_loop.quit();
+                }
+            }
+        }
+    });
+
+    let _core_listener = core
+        .add_listener_local()
+        .error({
+            let main_loop = main_loop.downgrade();
+
+            move |id, _seq, res, message| {
+                tracing::warn!("PipeWire error on object {id}: {message} ({res})");
+
+                if id == pw::core::PW_ID_CORE {
+                    if let Some(main_loop) = main_loop.upgrade() {
+                        main_loop.quit();
+                    }
+                }
+            }
+        })
+        .register();
+
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let state = Rc::downgrade(&state);
+            let publish = publish.clone();
+
+            move |global| {
+                let (Some(registry), Some(state)) = (registry.upgrade(), state.upgrade()) else {
+                    return;
+                };
+
+                if let Err(err) = on_global(global, &registry, &state, &publish) {
+                    tracing::warn!("Failed to track PipeWire object {}: {err}", global.id);
+                }
+            }
+        })
+        .global_remove({
+            let state = Rc::downgrade(&state);
+            let publish = publish.clone();
+
+            move |id| {
+                let Some(state) = state.upgrade() else {
+                    return;
+                };
+
+                let removed = {
+                    let mut state = state.borrow_mut();
+                    state.devices.remove(&id);
+                    state.nodes.remove(&id).is_some() || state.links.remove(&id).is_some()
+                };
+
+                if removed {
+                    publish();
+                }
+            }
+        })
+        .register();
+
+    let _messages = messages.attach(main_loop.loop_(), {
+        let state = Rc::downgrade(&state);
+        let main_loop = main_loop.downgrade();
+
+        move |message| match message {
+            LoopMessage::Command(command) => {
+                let Some(state) = state.upgrade() else {
+                    return;
+                };
+
+                if let Err(err) = state.borrow().exec(command) {
+                    tracing::error!("Failed to execute audio command: {err}");
+                }
+            }
+            LoopMessage::Quit => {
+                if let Some(main_loop) = main_loop.upgrade() {
+                    main_loop.quit();
+                }
+            }
+        }
+    });
+
+    publish();
+
+    main_loop.run();
+
+    Ok(())
+}
+
+fn on_global(
+    global: &GlobalObject<&DictRef>,
+    registry: &Registry,
+    state: &Rc<RefCell<State>>,
+    publish: &Rc<impl Fn() + 'static>,
+) -> miette::Result<()> {
+    let Some(props) = global.props else {
+        return Ok(());
+    };
+
+    match global.type_ {
+        ObjectType::Node => {
+            let Some(kind) = props
+                .get("media.class")
+                .and_then(NodeKind::from_media_class)
+            else {
+                return Ok(());
+            };
+
+            let proxy: Node = registry.bind(global).into_diagnostic()?;
+            let id = global.id;
+
+            let listener = proxy
+                .add_listener_local()
+                .param({
+                    let state = Rc::downgrade(state);
+                    let publish = Rc::downgrade(publish);
+
+                    move |_seq, param_type, _index, _next, param| {
+                        if param_type != ParamType::Props {
+                            return;
+                        }
+
+                        let (Some(state), Some(publish), Some(param)) =
+                            (state.upgrade(), publish.upgrade(), param)
+                        else {
+                            return;
+                        };
+
+                        let changed = match state.borrow_mut().nodes.get_mut(&id) {
+                            Some(node) => parse_props(node, param),
+                            None => false,
+                        };
+
+                        if changed {
+                            publish();
+                        }
+                    }
+                })
+                .register();
+            proxy.subscribe_params(&[ParamType::Props]);
+
+            let name = props.get("node.name").unwrap_or_default().to_string();
+            let description = props
+                .get("node.description")
+                .or_else(|| props.get("node.nick"))
+                .unwrap_or("Unknown")
+                .to_string();
+
+            state.borrow_mut().nodes.insert(
+                id,
+                PwNode {
+                    proxy,
+                    _listener: listener,
+
+                    kind,
+                    name,
+                    description,
+                    app_name: props.get("application.name").map(ToString::to_string),
+                    icon_name: props
+                        .get("application.icon-name")
+                        .or_else(|| props.get("application.process.binary"))
+                        .map(ToString::to_string),
+                    camera: matches!(props.get("device.api"), Some("v4l2" | "libcamera"))
+                        || props.get("media.role") == Some("Camera"),
+                    route: props.get("device.id").and_then(|id| id.parse().ok()).zip(
+                        props
+                            .get("card.profile.device")
+                            .and_then(|device| device.parse().ok()),
+                    ),
+
+                    channels: 0,
+                    volume: 0,
+                    muted: false,
+                },
+            );
+        }
+        ObjectType::Device => {
+            let proxy: Device = registry.bind(global).into_diagnostic()?;
+            let id = global.id;
+
+            let listener = proxy
+                .add_listener_local()
+                .param({
+                    let state = Rc::downgrade(state);
+
+                    move |_seq, param_type, _index, _next, param| {
+                        if param_type != ParamType::Route {
+                            return;
+                        }
+
+                        let (Some(state), Some((index, route_device))) =
+                            (state.upgrade(), param.and_then(parse_route))
+                        else {
+                            return;
+                        };
+
+                        if let Some(device) = state.borrow_mut().devices.get_mut(&id) {
+                            device.routes.insert(route_device, index);
+                        }
+                    }
+                })
+                .register();
+            proxy.subscribe_params(&[ParamType::Route]);
+
+            state.borrow_mut().devices.insert(
+                id,
+                PwDevice {
+                    proxy,
+                    _listener: listener,
+                    routes: HashMap::new(),
+                },
+            );
+
+            // Routes don't show up in the published state
+            return Ok(());
+        }
+        ObjectType::Link => {
+            let node_id = |key| props.get(key).and_then(|id: &str| id.parse::<u32>().ok());
+
+            if let (Some(output), Some(input)) =
+                (node_id("link.output.node"), node_id("link.input.node"))
+            {
+                state.borrow_mut().links.insert(global.id, (output, input));
+            }
+        }
+        ObjectType::Metadata if props.get("metadata.name") == Some("default") => {
+            let metadata: Metadata = registry.bind(global).into_diagnostic()?;
+
+            let listener = metadata
+                .add_listener_local()
+                .property({
+                    let state = Rc::downgrade(state);
+                    let publish = Rc::downgrade(publish);
+
+                    move |_subject, key, _type, value| {
+                        let (Some(state), Some(publish)) = (state.upgrade(), publish.upgrade())
+                        else {
+                            return 0;
+                        };
+
+                        let name = value.and_then(default_node_name).unwrap_or_default();
+
+                        {
+                            let mut state = state.borrow_mut();
+                            match key {
+                                Some("default.audio.sink") => state.default_sink = name,
+                                Some("default.audio.source") => state.default_source = name,
+                                _ => return 0,
+                            }
+                        }
+
+                        publish();
+                        0
+                    }
+                })
+                .register();
+
+            state.borrow_mut().metadata = Some((metadata, listener));
+        }
+        _ => return Ok(()),
+    }
+
+    publish();
+    Ok(())
+}
+
+/// Default node metadata values look like `{ "name": "alsa_output.pci-0000_00_1f.3.analog-stereo" }`
+fn default_node_name(value: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(value).ok()?;
+    value.get("name")?.as_str().map(ToString::to_string)
+}
+
+/// Updates the node from its `Props` param, returning whether anything changed
+fn parse_props(node: &mut PwNode, param: &Pod) -> bool {
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return false;
+    };
+
+    let mut changed = false;
+
+    for property in object.properties {
+        match (property.key, property.value) {
+            (spa::sys::SPA_PROP_channelVolumes, Value::ValueArray(ValueArray::Float(volumes))) => {
+                if volumes.is_empty() {
+                    continue;
+                }
+
+                // Channel volumes are linear, the cubic root matches what mixers show
+                let linear = volumes.iter().sum::<f32>() / volumes.len() as f32;
+                let volume = (linear.cbrt() * 100.0).round() as u8;
+
+                changed |= node.volume != volume || node.channels as usize != volumes.len();
+                node.channels = volumes.len() as u8;
+                node.volume = volume;
+            }
+            (spa::sys::SPA_PROP_mute, Value::Bool(muted)) => {
+                changed |= node.muted != muted;
+                node.muted = muted;
+            }
+            _ => {}
+        }
+    }
+
+    changed
+}
+
+/// Index and route device of an active route
+fn parse_route(param: &Pod) -> Option<(i32, i32)> {
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return None;
+    };
+
+    let int = |key| {
+        object
+            .properties
+            .iter()
+            .find_map(|property| match (property.key == key, &property.value) {
+                (true, Value::Int(value)) => Some(*value),
+                _ => None,
+            })
+    };
+
+    Some((
+        int(spa::sys::SPA_PARAM_ROUTE_index)?,
+        int(spa::sys::SPA_PARAM_ROUTE_device)?,
+    ))
+}
+
+fn mute_props(mute: bool) -> Vec<Property> {
+    vec![Property::new(spa::sys::SPA_PROP_mute, Value::Bool(mute))]
+}
+
+fn volume_props(channels: u8, percent: u8) -> Vec<Property> {
+    // The inverse of the cubic mapping in `parse_props`
+    let volume = (percent as f32 / 100.0).powi(3);
+
+    vec![Property::new(
+        spa::sys::SPA_PROP_channelVolumes,
+        Value::ValueArray(ValueArray::Float(vec![volume; channels.max(1) as usize])),
+    )]
+}
+
+fn set_props(node: &Node, properties: Vec<Property>) -> miette::Result<()> {
+    let props = Object {
+        type_: spa::sys::SPA_TYPE_OBJECT_Props,
+        id: spa::sys::SPA_PARAM_Props,
+        properties,
+    };
+
+    with_pod(props, |pod| node.set_param(ParamType::Props, 0, pod))
+}
+
+/// Serializes the object for `set`, as the pod borrows the buffer it lives in
+fn with_pod(object: Object, set: impl FnOnce(&Pod)) -> miette::Result<()> {
+    let (bytes, _) = PodSerializer::serialize(Cursor::new(Vec::new()), &Value::Object(object))
+        .map_err(|err| miette::miette!("Failed to serialize PipeWire param: {err:?}"))?;
+    let bytes = bytes.into_inner();
+    let pod = Pod::from_bytes(&bytes)
+        .ok_or_else(|| miette::miette!("Serialized PipeWire param isn't a valid pod"))?;
+
+    set(pod);
+
+    Ok(())
+}
@@ -0,0 +1,633 @@
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    io::Cursor,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use iced::futures::{channel::mpsc::Sender, SinkExt, Stream};
+use miette::IntoDiagnostic;
+use pulseaudio::protocol;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+    sync::{mpsc, oneshot, Mutex},
+};
+
+use crate::module::retry::{Backoff, RetryPolicy};
+
+use super::{
+    AudioBackendEvent, AudioCommand, AudioDevice, AudioDeviceKind, AudioInfo, AudioServerInfo,
+    AudioStream, EVENT_DEBOUNCE,
+};
+
+const DESCRIPTOR_SIZE: usize = 20;
+
+/// Requests waiting for their reply by sequence number, `None` once the connection is closed
+type PendingReplies = Arc<std::sync::Mutex<Option<HashMap<u32, oneshot::Sender<Vec<u8>>>>>>;
+
+/// Keeps a connection to the PulseAudio server alive, reconnecting with backoff,
+/// and streams the server state to the app
+pub fn connect(retry: RetryPolicy) -> impl Stream<Item = AudioBackendEvent> {
+    iced::stream::channel(16, move |mut output| async move {
+        let (commands_tx, mut commands) = mpsc::unbounded_channel();
+        if output
+            .send(AudioBackendEvent::Ready(commands_tx))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut backoff = Backoff::new(retry);
+
+        loop {
+            match PulseClient::connect().await {
+                Ok((client, events)) => {
+                    tracing::info!("Connected to PulseAudio");
+                    backoff.reset();
+
+                    match serve(&client, events, &mut commands, &mut output).await {
+                        Ok(()) => return,
+                        Err(err) => tracing::warn!("Lost connection to PulseAudio: {err}"),
+                    }
+                }
+                Err(err) => tracing::warn!("Failed to connect to PulseAudio: {err}"),
+            }
+
+            if output.send(AudioBackendEvent::Disconnected).await.is_err() {
+                return;
+            }
+
+            let delay = backoff.fail_forever();
+            tracing::debug!(
+                "Reconnecting to PulseAudio in {delay:?}, {} failure(s) in a row",
+                backoff.failures()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    })
+}
+
+/// Pushes state updates until the connection drops (`Err`) or the app goes away (`Ok`)
+async fn serve(
+    client: &PulseClient,
+    mut events: mpsc::UnboundedReceiver<()>,
+    commands: &mut mpsc::UnboundedReceiver<AudioCommand>,
+    output: &mut Sender<AudioBackendEvent>,
+) -> miette::Result<()> {
+    client
+        .request_ack(protocol::Command::Subscribe(
+            protocol::SubscriptionMask::ALL,
+        ))
+        .await?;
+
+    let mut info = Arc::new(client.info().await?);
+    if output
+        .send(AudioBackendEvent::Data(info.clone()))
+        .await
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => {
+                let Some(command) = command else {
+                    return Ok(());
+                };
+
+                if let Err(err) = client.exec(command, &info).await {
+                    tracing::error!("Failed to execute audio command: {err}");
+                }
+
+                continue;
+            }
+            event = events.recv() => {
+                if event.is_none() {
+                    return Err(miette::miette!("Connection closed by the server"));
+                }
+
+                tokio::time::sleep(EVENT_DEBOUNCE).await;
+                while events.try_recv().is_ok() {}
+            }
+        }
+
+        info = Arc::new(client.info().await?);
+        if output
+            .send(AudioBackendEvent::Data(info.clone()))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Native protocol client, matching replies to their requests by sequence number
+pub struct PulseClient {
+    writer: Mutex<OwnedWriteHalf>,
+    pending: PendingReplies,
+    seq: AtomicU32,
+    protocol_version: u16,
+}
+
+impl PulseClient {
+    /// Connects and authenticates, returning the client
+    /// and a channel that fires on every server event and closes with the connection
+    pub async fn connect() -> miette::Result<(Self, mpsc::UnboundedReceiver<()>)> {
+        let socket_path = pulseaudio::socket_path_from_env()
+            .ok_or_else(|| miette::miette!("PulseAudio is not available"))?;
+        let (reader, writer) = UnixStream::connect(socket_path)
+            .await
+            .into_diagnostic()?
+            .into_split();
+
+        let pending = PendingReplies::new(std::sync::Mutex::new(Some(HashMap::new())));
+        let (events_tx, events) = mpsc::unbounded_channel();
+        tokio::spawn(read_loop(reader, pending.clone(), events_tx));
+
+        let mut client = Self {
+            writer: Mutex::new(writer),
+            pending,
+            seq: AtomicU32::new(0),
+            protocol_version: protocol::MAX_VERSION,
+        };
+
+        let cookie = tokio::fs::read(
+            pulseaudio::cookie_path_from_env()
+                .ok_or_else(|| miette::miette!("Failed to get cookie path"))?,
+        )
+        .await
+        .into_diagnostic()?;
+        let auth = protocol::AuthParams {
+            version: protocol::MAX_VERSION,
+            supports_shm: false,
+            supports_memfd: false,
+            cookie,
+        };
+
+        let auth_info = client
+            .request::<protocol::AuthReply>(protocol::Command::Auth(auth))
+            .await?;
+        client.protocol_version = std::cmp::min(protocol::MAX_VERSION, auth_info.version);
+
+        let mut props = protocol::Props::new();
+        props.set(
+            protocol::Prop::ApplicationName,
+            CString::new("rbar").into_diagnostic()?,
+        );
+        client
+            .request::<protocol::SetClientNameReply>(protocol::Command::SetClientName(props))
+            .await?;
+
+        Ok((client, events))
+    }
+
+    pub async fn info(&self) -> miette::Result<AudioInfo> {
+        let server_info = self
+            .request::<protocol::command::ServerInfo>(protocol::Command::GetServerInfo)
+            .await?;
+
+        let sinks = self
+            .request::<protocol::SinkInfoList>(protocol::Command::GetSinkInfoList)
+            .await?
+            .into_iter()
+            .map(AudioDevice::from_sink)
+            .collect::<miette::Result<Vec<_>>>()?;
+
+        let sources = self
+            .request::<protocol::SourceInfoList>(protocol::Command::GetSourceInfoList)
+            .await?
+            .into_iter()
+            .map(AudioDevice::from_source)
+            .collect::<miette::Result<Vec<_>>>()?;
+
+        let sink_inputs = self
+            .request::<protocol::SinkInputInfoList>(protocol::Command::GetSinkInputInfoList)
+            .await?
+            .into_iter()
+            .map(AudioStream::from_sink_input)
+            .collect();
+
+        let source_outputs = self
+            .request::<protocol::SourceOutputInfoList>(protocol::Command::GetSourceOutputInfoList)
+            .await?
+            .into_iter()
+            .map(AudioStream::from_source_output)
+            .collect();
+
+        Ok(AudioInfo {
+            server_info: AudioServerInfo::from_server_info(&server_info),
+            sinks,
+            sources,
+            sink_inputs,
+            source_outputs,
+            video_streams: None,
+        })
+    }
+
+    pub async fn exec(&self, command: AudioCommand, info: &AudioInfo) -> miette::Result<()> {
+        match command {
+            AudioCommand::SetDefault {
+                kind,
+                name,
+                move_streams,
+            } => {
+                tracing::debug!("Setting default {kind} to {name}");
+
+                let device_name = CString::new(name.as_str()).into_diagnostic()?;
+
+                match kind {
+                    AudioDeviceKind::Sink => {
+                        self.request_ack(protocol::Command::SetDefaultSink(device_name.clone()))
+                            .await?;
+
+                        if move_streams {
+                            for sink_input in &info.sink_inputs {
+                                self.request_ack(protocol::Command::MoveSinkInput(
+                                    protocol::MoveStreamParams {
+                                        index: sink_input.index,
+                                        device_index: None,
+                                        device_name: Some(device_name.clone()),
+                                    },
+                                ))
+                                .await?;
+                            }
+                        }
+                    }
+                    AudioDeviceKind::Source => {
+                        self.request_ack(protocol::Command::SetDefaultSource(device_name.clone()))
+                            .await?;
+
+                        if move_streams {
+                            let source_outputs = self
+                                .request::<protocol::SourceOutputInfoList>(
+                                    protocol::Command::GetSourceOutputInfoList,
+                                )
+                                .await?;
+
+                            for source_output in source_outputs {
+                                self.request_ack(protocol::Command::MoveSourceOutput(
+                                    protocol::MoveStreamParams {
+                                        index: source_output.index,
+                                        device_index: None,
+                                        device_name: Some(device_name.clone()),
+                                    },
+                                ))
+                                .await?;
+                            }
+                        }
+                    }
+                }
+            }
+            AudioCommand::SetMute { kind, name, mute } => {
+                tracing::debug!("Setting {kind} {name} mute to {mute}");
+
+                let params = protocol::SetDeviceMuteParams {
+                    device_index: None,
+                    device_name: Some(CString::new(name).into_diagnostic()?),
+                    mute,
+                };
+
+                self.request_ack(match kind {
+                    AudioDeviceKind::Sink => protocol::Command::SetSinkMute(params),
+                    AudioDeviceKind::Source => protocol::Command::SetSourceMute(params),
+                })
+                .await?;
+            }
+            AudioCommand::SetVolume { kind, name, volume } => {
+                tracing::debug!("Setting {kind} {name} volume to {volume}%");
+
+                let device = info
+                    .devices(kind)
+                    .iter()
+                    .find(|d| d.name == name)
+                    .ok_or_else(|| miette::miette!("Unknown audio device {name}"))?;
+
+                let params = protocol::SetDeviceVolumeParams {
+                    device_index: None,
+                    device_name: Some(CString::new(name).into_diagnostic()?),
+                    volume: channel_volume(device.channels, volume),
+                };
+
+                self.request_ack(match kind {
+                    AudioDeviceKind::Sink => protocol::Command::SetSinkVolume(params),
+                    AudioDeviceKind::Source => protocol::Command::SetSourceVolume(params),
+                })
+                .await?;
+            }
+            AudioCommand::SetStreamMute { index, mute } => {
+                tracing::debug!("Setting stream {index} mute to {mute}");
+
+                self.request_ack(protocol::Command::SetSinkInputMute(
+                    protocol::SetStreamMuteParams { index, mute },
+                ))
+                .await?;
+            }
+            AudioCommand::SetStreamVolume { index, volume } => {
+                tracing::debug!("Setting stream {index} volume to {volume}%");
+
+                let stream = info
+                    .sink_inputs
+                    .iter()
+                    .find(|s| s.index == index)
+                    .ok_or_else(|| miette::miette!("Unknown audio stream {index}"))?;
+
+                self.request_ack(protocol::Command::SetSinkInputVolume(
+                    protocol::SetStreamVolumeParams {
+                        index,
+                        volume: channel_volume(stream.channels, volume),
+                    },
+                ))
+                .await?;
+            }
+            AudioCommand::MoveStream { index, sink } => {
+                tracing::debug!("Moving stream {index} to {sink}");
+
+                self.request_ack(protocol::Command::MoveSinkInput(
+                    protocol::MoveStreamParams {
+                        index,
+                        device_index: None,
+                        device_name: Some(CString::new(sink).into_diagnostic()?),
+                    },
+                ))
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn request<R>(&self, command: protocol::Command) -> miette::Result<R>
+    where
+        R: protocol::CommandReply,
+    {
+        let mut reply = self.send(command).await?;
+        let (_, reply) = protocol::read_reply_message::<R>(&mut reply, self.protocol_version)
+            .into_diagnostic()?;
+
+        Ok(reply)
+    }
+
+    pub async fn request_ack(&self, command: protocol::Command) -> miette::Result<()> {
+        let mut reply = self.send(command).await?;
+        protocol::read_ack_message(&mut reply).into_diagnostic()?;
+
+        Ok(())
+    }
+
+    async fn send(&self, command: protocol::Command) -> miette::Result<Cursor<Vec<u8>>> {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+
+        let mut message = vec![];
+        protocol::write_command_message(&mut message, seq, command, self.protocol_version)
+            .into_diagnostic()?;
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        // Nothing would ever answer a request sent after the read loop stopped
+        self.pending
+            .lock()
+            .expect("PulseAudio pending replies lock poisoned")
+            .as_mut()
+            .ok_or_else(|| miette::miette!("PulseAudio connection closed"))?
+            .insert(seq, reply_tx);
+
+        self.writer
+            .lock()
+            .await
+            .write_all(&message)
+            .await
+            .into_diagnostic()?;
+
+        let reply = reply_rx
+            .await
+            .map_err(|_| miette::miette!("PulseAudio connection closed"))?;
+
+        Ok(Cursor::new(reply))
+    }
+}
+
+/// Reads whole frames off the socket, handing replies to whoever waits on their sequence number
+/// and reporting everything else (subscription events) as a server event
+async fn read_loop(
+    mut reader: OwnedReadHalf,
+    pending: PendingReplies,
+    events: mpsc::UnboundedSender<()>,
+) {
+    loop {
+        let frame = match read_frame(&mut reader).await {
+            Ok(frame) => frame,
+            Err(err) => {
+                tracing::debug!("PulseAudio read loop stopped: {err}");
+                break;
+            }
+        };
+
+        let reply_tx = frame_seq(&frame).and_then(|seq| {
+            pending
+                .lock()
+                .expect("PulseAudio pending replies lock poisoned")
+                .as_mut()?
+                .remove(&seq)
+        });
+
+        match reply_tx {
+            Some(reply_tx) => {
+                let _ = reply_tx.send(frame);
+            }
+            None => {
+                if events.send(()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Dropping the senders fails every request still in flight,
+    // and later ones fail right away under the same lock
+    pending
+        .lock()
+        .expect("PulseAudio pending replies lock poisoned")
+        .take();
+}
+
+async fn read_frame(reader: &mut OwnedReadHalf) -> std::io::Result<Vec<u8>> {
+    let mut frame = vec![0; DESCRIPTOR_SIZE];
+    reader.read_exact(&mut frame).await?;
+
+    let length = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+    frame.resize(DESCRIPTOR_SIZE + length, 0);
+    reader.read_exact(&mut frame[DESCRIPTOR_SIZE..]).await?;
+
+    Ok(frame)
+}
+
+/// Every command payload starts with two tagged u32s: the command and its sequence number
+fn frame_seq(frame: &[u8]) -> Option<u32> {
+    let seq = frame.get(DESCRIPTOR_SIZE + 6..DESCRIPTOR_SIZE + 10)?;
+    Some(u32::from_be_bytes([seq[0], seq[1], seq[2], seq[3]]))
+}
+
+impl AudioServerInfo {
+    fn from_server_info(server: &protocol::ServerInfo) -> Self {
+        let name = |name: &Option<CString>| {
+            name.as_ref()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+
+        Self {
+            default_sink: name(&server.default_sink_name),
+            default_source: name(&server.default_source_name),
+        }
+    }
+}
+
+impl AudioDevice {
+    fn from_sink(sink: protocol::SinkInfo) -> miette::Result<Self> {
+        Self::from_protocol(
+            AudioDeviceKind::Sink,
+            sink.index,
+            &sink.name,
+            sink.description.as_deref(),
+            false,
+            sink.muted,
+            &sink.cvolume,
+        )
+    }
+
+    fn from_source(source: protocol::SourceInfo) -> miette::Result<Self> {
+        Self::from_protocol(
+            AudioDeviceKind::Source,
+            source.index,
+            &source.name,
+            source.description.as_deref(),
+            source.monitor_of_sink_index.is_some(),
+            source.muted,
+            &source.cvolume,
+        )
+    }
+
+    fn from_protocol(
+        kind: AudioDeviceKind,
+        index: u32,
+        name: &CStr,
+        description: Option<&CStr>,
+        monitor: bool,
+        muted: bool,
+        cvolume: &protocol::ChannelVolume,
+    ) -> miette::Result<Self> {
+        let name = name.to_str().into_diagnostic()?.to_string();
+        let description = match description {
+            Some(str) => Some(str.to_str().into_diagnostic()?.to_string()),
+            None => None,
+        }
+        .unwrap_or_else(|| "Unknown".into());
+
+        let volume = volume_percent(cvolume);
+
+        tracing::debug!("{kind} {name} ({description}) [{muted} {volume}]");
+
+        Ok(Self {
+            kind,
+            index,
+            channels: cvolume.channels().len() as u8,
+
+            name,
+            description,
+            monitor,
+
+            muted,
+            volume,
+        })
+    }
+}
+
+impl AudioStream {
+    fn from_sink_input(sink_input: protocol::SinkInputInfo) -> Self {
+        let app_name = prop_string(&sink_input.props, protocol::Prop::ApplicationName)
+            .unwrap_or_else(|| sink_input.name.to_string_lossy().into_owned());
+        let icon_name = prop_string(&sink_input.props, protocol::Prop::ApplicationIconName);
+
+        let volume = volume_percent(&sink_input.cvolume);
+
+        tracing::debug!(
+            "Stream {} {app_name} -> {} [{} {volume}]",
+            sink_input.index,
+            sink_input.sink_index,
+            sink_input.muted
+        );
+
+        Self {
+            index: sink_input.index,
+            device_index: sink_input.sink_index,
+            channels: sink_input.cvolume.channels().len() as u8,
+
+            app_name,
+            icon_name,
+
+            muted: sink_input.muted,
+            volume,
+        }
+    }
+
+    fn from_source_output(source_output: protocol::SourceOutputInfo) -> Self {
+        let app_name = prop_string(&source_output.props, protocol::Prop::ApplicationName)
+            .unwrap_or_else(|| source_output.name.to_string_lossy().into_owned());
+        let icon_name = prop_string(&source_output.props, protocol::Prop::ApplicationIconName);
+
+        Self {
+            index: source_output.index,
+            device_index: source_output.source_index,
+            channels: source_output.cvolume.channels().len() as u8,
+
+            app_name,
+            icon_name,
+
+            muted: source_output.muted,
+            volume: volume_percent(&source_output.cvolume),
+        }
+    }
+}
+
+fn prop_string(props: &protocol::Props, prop: protocol::Prop) -> Option<String> {
+    let bytes = props.get(prop)?;
+    let str = CStr::from_bytes_until_nul(bytes)
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_else(|_| String::from_utf8_lossy(bytes));
+
+    Some(str.into_owned())
+}
+
+/// Volumes are percentages of the nominal volume, like `pactl` and pavucontrol show them
+fn channel_volume(channels: u8, percent: u8) -> protocol::ChannelVolume {
+    let mut cvolume = protocol::ChannelVolume::default();
+    let raw = protocol::Volume::NORM.as_u32() as u64 * percent as u64 / 100;
+    let volume = protocol::Volume::from_u32_clamped(raw as u32);
+
+    for _ in 0..channels.max(1) {
+        cvolume.push(volume);
+    }
+
+    cvolume
+}
+
+fn volume_percent(cvolume: &protocol::ChannelVolume) -> u8 {
+    let channels = cvolume.channels();
+
+    if channels.is_empty() {
+        return 0;
+    }
+
+    let norm = protocol::Volume::NORM.as_u32() as f32;
+    let sum = channels.iter().map(|v| v.as_u32() as f32).sum::<f32>();
+    (sum / channels.len() as f32 / norm * 100.0).round() as u8
+}
@@ -0,0 +1,286 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use blight::Device;
+use derive_more::derive::Display;
+use iced::{
+    widget::{mouse_area, rich_text, span},
+    Element, Renderer, Theme,
+};
+use iced_fonts::{nerd, Nerd};
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use tokio::{
+    io::{unix::AsyncFd, Interest},
+    time::Instant,
+};
+
+use super::{
+    audio::ScrollDirection, Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput,
+};
+
+const BACKLIGHT_DIR: &str = "/sys/class/backlight";
+
+/// The kernel notifies `actual_brightness` pollers of changes through sysfs and hotkeys,
+/// but not every driver does when the firmware changes the brightness on its own,
+/// so it gets read again this often as well
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Keeps an eye on the brightness of a backlight device, and changes it on scroll
+pub struct Backlight {
+    config: BacklightConfig,
+    device: Device,
+    /// `actual_brightness` of the device, polled for changes
+    notify: Option<AsyncFd<File>>,
+    interval: Duration,
+    next: Instant,
+    /// Last brightness handed to the widget, in raw device units
+    shown: Option<u32>,
+}
+
+impl Backlight {
+    fn read(&mut self) -> BacklightData {
+        self.device.reload();
+        self.shown = Some(self.device.brightness());
+
+        BacklightData {
+            device: self.device.name().to_string(),
+            brightness: self.device.brightness(),
+            max: self.device.max(),
+        }
+    }
+
+    /// Waits for the kernel to flag `actual_brightness` as changed. Cancel safe
+    async fn notified(notify: &mut AsyncFd<File>) -> std::io::Result<()> {
+        let mut guard = notify.ready_mut(Interest::PRIORITY).await?;
+
+        // Reading the attribute again is what arms the next notification
+        let file = guard.get_inner_mut();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut vec![])?;
+
+        guard.clear_ready();
+        Ok(())
+    }
+
+    /// Moves the brightness by a step, without going under the floor
+    fn change(&mut self, direction: ScrollDirection) -> miette::Result<()> {
+        let max = self.device.max() as u64;
+        let step = (max * self.config.step as u64).div_ceil(100).max(1);
+        let floor = (max * self.config.min.min(100) as u64).div_ceil(100);
+
+        self.device.reload();
+        let current = self.device.brightness() as u64;
+        let value = match direction {
+            ScrollDirection::Up => current.saturating_add(step).min(max),
+            ScrollDirection::Down => current.saturating_sub(step).max(floor),
+        };
+
+        self.device
+            .write_value(value as u32)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to set the brightness of {}", self.device.name()))
+    }
+}
+
+impl Module for Backlight {
+    type Config = BacklightConfig;
+    type Event = BacklightEvent;
+    type Output = BacklightData;
+
+    type Widget = BacklightWidget;
+
+    fn new(config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        let device = Device::new(config.device.as_deref().map(Into::into))
+            .into_diagnostic()
+            .wrap_err_with(|| match &config.device {
+                Some(name) => format!("Failed to open the backlight device {name}"),
+                None => "Failed to find a backlight device".into(),
+            })?;
+
+        let interval = context.retry.refresh_interval(REFRESH_INTERVAL);
+
+        let path = Path::new(BACKLIGHT_DIR)
+            .join(device.name())
+            .join("actual_brightness");
+        let notify = File::open(&path)
+            .and_then(|mut file| {
+                // Notifications only come once the attribute has been read
+                file.read_to_end(&mut vec![])?;
+                AsyncFd::with_interest(file, Interest::PRIORITY)
+            })
+            .inspect_err(|err| {
+                tracing::warn!("Failed to watch {}, polling it: {err}", path.display())
+            })
+            .ok();
+
+        Ok(Self {
+            config,
+            device,
+            notify,
+            interval,
+            next: Instant::now() + interval,
+            shown: None,
+        })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        Ok(self.read())
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        loop {
+            // Sleeping until a fixed deadline keeps this cancel safe
+            let refresh = tokio::time::sleep_until(self.next);
+
+            match &mut self.notify {
+                Some(notify) => tokio::select! {
+                    res = Self::notified(notify) => {
+                        if let Err(err) = res {
+                            tracing::warn!("Stopped watching the brightness, polling it: {err}");
+                            self.notify = None;
+                        }
+                    }
+                    _ = refresh => self.next = Instant::now() + self.interval,
+                },
+                None => {
+                    refresh.await;
+                    self.next = Instant::now() + self.interval;
+                }
+            }
+
+            self.device.reload();
+            if self.shown != Some(self.device.brightness()) {
+                return Ok(BacklightEvent::Changed);
+            }
+        }
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            BacklightEvent::Changed => {}
+            BacklightEvent::Scroll(direction) => {
+                // Usually missing write access to sysfs, which shouldn't stop the module
+                if let Err(err) = self.change(direction) {
+                    tracing::warn!("{err:?}");
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(self.read()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BacklightEvent {
+    Changed,
+    Scroll(ScrollDirection),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BacklightData {
+    pub device: String,
+    pub brightness: u32,
+    pub max: u32,
+}
+
+impl BacklightData {
+    pub fn percent(&self) -> u8 {
+        match self.max {
+            0 => 0,
+            max => (self.brightness as f64 * 100.0 / max as f64).round() as u8,
+        }
+    }
+
+    fn icon(&self) -> Nerd {
+        match self.percent() {
+            0..34 => Nerd::BrightnessFive,
+            34..67 => Nerd::BrightnessSix,
+            _ => Nerd::BrightnessSeven,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BacklightWidget(Option<BacklightData>);
+
+impl ModuleWidget<Backlight> for BacklightWidget {
+    type Event = BacklightWidgetEvent;
+
+    fn new(_config: BacklightConfig) -> Self {
+        Self::default()
+    }
+
+    fn set(&mut self, data: BacklightData) {
+        self.0 = Some(data);
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Backlight>> {
+        match event {
+            BacklightWidgetEvent::Scroll(direction) => Some(ModuleWidgetUpdateOutput::module(
+                BacklightEvent::Scroll(direction),
+            )),
+        }
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let (icon, label) = match &self.0 {
+            Some(data) => (data.icon(), format!(" {}%", data.percent())),
+            None => (Nerd::BrightnessFive, " -".into()),
+        };
+
+        mouse_area(rich_text![
+            span(nerd::icon_to_string(icon)).font(iced_fonts::NERD_FONT),
+            span(label)
+        ])
+        .on_scroll(|delta| BacklightWidgetEvent::Scroll(ScrollDirection::from_delta(delta)))
+        .into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let data = self.0.as_ref()?;
+
+        Some(format!(
+            "{}: {}% ({}/{})",
+            data.device,
+            data.percent(),
+            data.brightness,
+            data.max
+        ))
+    }
+
+    fn state(&self) -> serde_json::Value {
+        match &self.0 {
+            Some(data) => serde_json::json!({
+                "device": data.device,
+                "percent": data.percent(),
+            }),
+            None => serde_json::Value::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BacklightWidgetEvent {
+    Scroll(ScrollDirection),
+}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Backlight({})", device.as_deref().unwrap_or("auto"))]
+#[serde(default)]
+pub struct BacklightConfig {
+    /// Device name under `/sys/class/backlight` (e.g. `intel_backlight`), found automatically when unset
+    pub device: Option<String>,
+    /// Brightness change per scroll step, in percent
+    #[default = 5]
+    pub step: u8,
+    /// Lowest brightness scrolling goes down to, in percent, so the screen never goes dark
+    #[default = 1]
+    pub min: u8,
+}
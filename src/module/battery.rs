@@ -1,33 +1,86 @@
-use iced::widget::{rich_text, row, span};
+use std::time::Duration;
+
+use iced::{
+    widget::{rich_text, row, span},
+    Element, Renderer, Theme,
+};
 use iced_fonts::{nerd, Nerd};
-use miette::IntoDiagnostic;
-use starship_battery::{Battery as SBattery, Manager, State};
+use miette::{Context, IntoDiagnostic};
+use serde::Deserialize;
+use starship_battery::{units::time::second, Battery as SBattery, Manager, State};
 
-use crate::app::AppMsg;
+use super::{Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput, NoConfig};
 
-use super::{NoConfig, TModule};
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
 
+/// Re-reads the batteries periodically, leaving retries to the module runner
 #[derive(Debug)]
-pub struct Battery(Vec<BatteryData>);
+pub struct Battery {
+    refresh: Duration,
+}
+
+impl Battery {
+    fn read(&self) -> miette::Result<Vec<BatteryData>> {
+        BatteryInfo::init()
+            .map(|info| info.data().collect())
+            .wrap_err("Failed to read the batteries")
+    }
+}
 
-impl TModule for Battery {
+impl Module for Battery {
     type Config = NoConfig;
     type Event = BatteryEvent;
+    type Output = Vec<BatteryData>;
 
-    fn new(_config: Self::Config) -> Self {
-        Self(vec![])
+    type Widget = BatteryWidget;
+
+    fn new(_config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        Ok(Self {
+            refresh: context.retry.refresh_interval(REFRESH_INTERVAL),
+        })
     }
 
-    fn update(&mut self, event: Self::Event) -> Option<AppMsg> {
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        self.read()
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        tokio::time::sleep(self.refresh).await;
+        Ok(BatteryEvent::Refresh)
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
         match event {
-            BatteryEvent::SetData(data) => self.0 = data,
+            BatteryEvent::Refresh => self.read().map(Some),
         }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatteryEvent {
+    Refresh,
+}
+
+#[derive(Debug)]
+pub struct BatteryWidget(Vec<BatteryData>);
 
-        None
+impl ModuleWidget<Battery> for BatteryWidget {
+    type Event = BatteryWidgetEvent;
+
+    fn new(_config: NoConfig) -> Self {
+        Self(vec![])
     }
 
-    fn view(&self) -> iced::Element<'_, Self::Event, iced::Theme, iced::Renderer> {
-        row(self.0.iter().map(|BatteryData { level, icon }| {
+    fn set(&mut self, data: Vec<BatteryData>) {
+        self.0 = data;
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Battery>> {
+        match event {}
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        row(self.0.iter().map(|BatteryData { level, icon, .. }| {
             rich_text![
                 span(nerd::icon_to_string(*icon)).font(iced_fonts::NERD_FONT),
                 span(format!(" {level}%"))
@@ -37,13 +90,44 @@ impl TModule for Battery {
         .spacing(5)
         .into()
     }
-}
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum BatteryEvent {
-    SetData(Vec<BatteryData>),
+    fn tooltip(&self) -> Option<String> {
+        let lines = self.0.iter().enumerate().map(|(i, battery)| {
+            let BatteryData {
+                level,
+                state,
+                remaining,
+                ..
+            } = battery;
+
+            let status = match (state, remaining) {
+                (State::Charging, Some(time)) => format!("charging, full in {}", duration(*time)),
+                (State::Discharging, Some(time)) => format!("{} remaining", duration(*time)),
+                (State::Charging, None) => "charging".into(),
+                (State::Discharging, None) => "discharging".into(),
+                (State::Full, _) => "full".into(),
+                (State::Empty, _) => "empty".into(),
+                (State::Unknown, _) => "not charging".into(),
+            };
+
+            format!("Battery {}: {level}%, {status}", i + 1)
+        });
+
+        let tip = lines.collect::<Vec<_>>().join("\n");
+        (!tip.is_empty()).then_some(tip)
+    }
+
+    fn state(&self) -> serde_json::Value {
+        self.0
+            .iter()
+            .map(|BatteryData { level, .. }| *level)
+            .collect()
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum BatteryWidgetEvent {}
+
 #[derive(Debug)]
 pub struct BatteryInfo {
     batteries: Vec<SBattery>,
@@ -91,18 +175,34 @@ impl BatteryInfo {
                     90..100 => Nerd::BatteryChargingNinezero,
                     _ => Nerd::BatteryCharging,
                 },
-                State::Empty => todo!(),
-                State::Full => todo!(),
+                State::Empty => Nerd::BatteryOutline,
+                State::Full => Nerd::Battery,
             };
 
-            BatteryData { level, icon }
+            let remaining = match battery.state() {
+                State::Charging => battery.time_to_full(),
+                State::Discharging => battery.time_to_empty(),
+                _ => None,
+            }
+            .and_then(|time| Duration::try_from_secs_f32(time.get::<second>()).ok());
+
+            BatteryData {
+                level,
+                icon,
+                state: battery.state(),
+                remaining,
+            }
         })
     }
 }
 
-impl PartialEq for BatteryInfo {
-    fn eq(&self, _other: &Self) -> bool {
-        false
+/// `1h 05m`, or just the minutes when there's less than an hour left
+fn duration(time: Duration) -> String {
+    let minutes = time.as_secs() / 60;
+
+    match minutes / 60 {
+        0 => format!("{minutes}m"),
+        hours => format!("{hours}h {:02}m", minutes % 60),
     }
 }
 
@@ -110,6 +210,9 @@ impl PartialEq for BatteryInfo {
 pub struct BatteryData {
     level: u8,
     icon: Nerd,
+    state: State,
+    /// Until the battery is full while charging, or empty while discharging
+    remaining: Option<Duration>,
 }
 
 macro_rules! battery_icons_eq {
@@ -134,13 +237,22 @@ macro_rules! battery_icons_eq {
 
 impl PartialEq for BatteryData {
     fn eq(&self, other: &Self) -> bool {
-        let Self { level, icon } = self;
+        let Self {
+            level,
+            icon,
+            state,
+            remaining,
+        } = self;
         let Self {
             level: other_level,
             icon: other_icon,
+            state: other_state,
+            remaining: other_remaining,
         } = other;
 
         (level == other_level)
+            && (state == other_state)
+            && (remaining == other_remaining)
             && (battery_icons_eq!(
             icon,
             other_icon =>
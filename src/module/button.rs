@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use derive_more::derive::Display;
+use iced::{
+    widget::{button, column, container, image, mouse_area, row, svg, text},
+    Alignment, Element, Length, Renderer, Theme,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::AppMsg,
+    util::{find_icon, spawn_command},
+};
+
+use super::{
+    audio::ScrollDirection, Module, ModuleContext, ModuleEvent, ModuleWidget,
+    ModuleWidgetUpdateOutput,
+};
+
+/// Runs the commands of the button clicks, it has no data source of its own
+#[derive(Debug)]
+pub struct Button;
+
+impl Module for Button {
+    type Config = ButtonConfig;
+    type Event = ButtonEvent;
+    type Output = ();
+
+    type Widget = ButtonWidget;
+
+    fn new(_config: Self::Config, _context: ModuleContext) -> miette::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        Ok(())
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        std::future::pending().await
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            ButtonEvent::Run(command) => spawn_command(&command),
+        }
+
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtonEvent {
+    Run(String),
+}
+
+#[derive(Debug)]
+pub struct ButtonWidget {
+    config: ButtonConfig,
+    /// Entries of the open popup menu
+    menu: Option<Vec<ButtonMenuEntry>>,
+    /// Icons of the button and of its menu entries by name, looked up once
+    icons: HashMap<String, ButtonIcon>,
+}
+
+impl ButtonWidget {
+    fn icon_view<'a, M: 'a>(&self, name: Option<&str>) -> Option<Element<'a, M, Theme, Renderer>> {
+        self.icons.get(name?).map(ButtonIcon::view)
+    }
+
+    fn act(&mut self, action: Option<ButtonAction>) -> Option<ModuleWidgetUpdateOutput<Button>> {
+        match action? {
+            ButtonAction::Command(command) => {
+                self.menu = None;
+                Some(ModuleWidgetUpdateOutput::module(ButtonEvent::Run(command)))
+            }
+            ButtonAction::Event(event) => {
+                self.menu = None;
+
+                match serde_json::from_str::<ModuleEvent>(&event) {
+                    Ok(event) => Some(ModuleWidgetUpdateOutput::app(AppMsg::Module(event))),
+                    Err(err) => {
+                        tracing::error!("Button event {event} isn't a valid module event: {err}");
+                        None
+                    }
+                }
+            }
+            ButtonAction::Popup(entries) => {
+                self.menu = match self.menu.take() {
+                    Some(_) => None,
+                    None => Some(entries),
+                };
+
+                None
+            }
+        }
+    }
+}
+
+impl ModuleWidget<Button> for ButtonWidget {
+    type Event = ButtonWidgetEvent;
+
+    fn new(config: ButtonConfig) -> Self {
+        let mut names = config.icon.iter().cloned().collect::<Vec<_>>();
+        let actions = [
+            &config.on_click,
+            &config.on_middle_click,
+            &config.on_right_click,
+            &config.on_scroll_up,
+            &config.on_scroll_down,
+        ];
+        for action in actions.into_iter().flatten() {
+            action.icons(&mut names);
+        }
+
+        let icons = names
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), ButtonIcon::find(&name)?)))
+            .collect();
+
+        Self {
+            config,
+            menu: None,
+            icons,
+        }
+    }
+
+    fn set(&mut self, _output: ()) {}
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Button>> {
+        let action = match event {
+            ButtonWidgetEvent::Click => self.config.on_click.clone(),
+            ButtonWidgetEvent::MiddleClick => self.config.on_middle_click.clone(),
+            ButtonWidgetEvent::RightClick => self.config.on_right_click.clone(),
+            ButtonWidgetEvent::Scroll(ScrollDirection::Up) => self.config.on_scroll_up.clone(),
+            ButtonWidgetEvent::Scroll(ScrollDirection::Down) => self.config.on_scroll_down.clone(),
+            ButtonWidgetEvent::Entry(i) => {
+                let action = self
+                    .menu
+                    .as_ref()
+                    .and_then(|entries| entries.get(i))
+                    .map(|entry| entry.action.clone());
+
+                // A nested menu replaces the open one, only the button itself toggles it
+                if let Some(ButtonAction::Popup(entries)) = action {
+                    self.menu = Some(entries);
+                    return None;
+                }
+
+                action
+            }
+        };
+
+        self.act(action)
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let ButtonConfig { label, icon, .. } = &self.config;
+
+        let content = row![]
+            .push_maybe(self.icon_view(icon.as_deref()))
+            .push_maybe(label.as_deref().map(text))
+            .spacing(5)
+            .align_y(Alignment::Center);
+
+        mouse_area(
+            button(content)
+                .style(button::secondary)
+                .on_press(ButtonWidgetEvent::Click),
+        )
+        .on_middle_press(ButtonWidgetEvent::MiddleClick)
+        .on_right_press(ButtonWidgetEvent::RightClick)
+        .on_scroll(|delta| ButtonWidgetEvent::Scroll(ScrollDirection::from_delta(delta)))
+        .into()
+    }
+
+    fn has_popup(&self) -> bool {
+        self.menu.is_some()
+    }
+
+    fn popup(&self) -> Option<Element<'_, Self::Event, Theme, Renderer>> {
+        let entries = self.menu.as_ref()?.iter().enumerate().map(|(i, entry)| {
+            button(
+                row![]
+                    .push_maybe(self.icon_view(entry.icon.as_deref()))
+                    .push(text(&entry.label))
+                    .spacing(5)
+                    .align_y(Alignment::Center),
+            )
+            .style(button::text)
+            .width(Length::Fill)
+            .on_press(ButtonWidgetEvent::Entry(i))
+            .into()
+        });
+
+        Some(
+            container(column(entries).spacing(5))
+                .padding(10)
+                .style(container::rounded_box)
+                .into(),
+        )
+    }
+
+    fn close_popup(&mut self) {
+        self.menu = None;
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::json!({
+            "label": self.config.label,
+            "menu_open": self.menu.is_some(),
+        })
+    }
+}
+
+/// Image of a freedesktop icon name or path, found when the widget is created
+/// since looking it up goes through the icon directories
+#[derive(Debug, Clone)]
+enum ButtonIcon {
+    Svg(svg::Handle),
+    Image(image::Handle),
+}
+
+impl ButtonIcon {
+    fn find(name: &str) -> Option<Self> {
+        let path = find_icon(name)?;
+
+        Some(match path.extension().is_some_and(|ext| ext == "svg") {
+            true => Self::Svg(svg::Handle::from_path(path)),
+            false => Self::Image(image::Handle::from_path(path)),
+        })
+    }
+
+    /// Rendered at the size of the text
+    fn view<'a, M: 'a>(&self) -> Element<'a, M, Theme, Renderer> {
+        match self {
+            Self::Svg(handle) => svg(handle.clone()).width(16).height(16).into(),
+            Self::Image(handle) => image(handle.clone()).width(16).height(16).into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ButtonWidgetEvent {
+    Click,
+    MiddleClick,
+    RightClick,
+    Scroll(ScrollDirection),
+    /// Click on an entry of the open popup menu, by its index
+    Entry(usize),
+}
+
+#[derive(Default, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Button({})", label.as_deref().or(icon.as_deref()).unwrap_or_default())]
+#[serde(default)]
+pub struct ButtonConfig {
+    /// Text of the button, can be a Nerd Font glyph
+    pub label: Option<String>,
+    /// Freedesktop icon name (e.g. `system-shutdown`) or path to an image, shown before the label
+    pub icon: Option<String>,
+    pub on_click: Option<ButtonAction>,
+    pub on_middle_click: Option<ButtonAction>,
+    pub on_right_click: Option<ButtonAction>,
+    pub on_scroll_up: Option<ButtonAction>,
+    pub on_scroll_down: Option<ButtonAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ButtonAction {
+    /// Shell command to run in the background, e.g. `Command("fuzzel")`
+    Command(String),
+    /// Module event in the IPC JSON format, sent to every instance of the module,
+    /// e.g. `Event("{\"Clock\": \"SwitchFormat\"}")`
+    Event(String),
+    /// Opens a popup menu with these entries, or closes it when it's open. From a menu entry,
+    /// it replaces the open menu with these entries
+    Popup(Vec<ButtonMenuEntry>),
+}
+
+impl ButtonAction {
+    /// Names of the icons in the menu, nested menus included
+    fn icons(&self, names: &mut Vec<String>) {
+        let Self::Popup(entries) = self else {
+            return;
+        };
+
+        for entry in entries {
+            names.extend(entry.icon.clone());
+            entry.action.icons(names);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ButtonMenuEntry {
+    pub label: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+    pub action: ButtonAction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(label: &str, action: ButtonAction) -> ButtonMenuEntry {
+        ButtonMenuEntry {
+            label: label.into(),
+            icon: None,
+            action,
+        }
+    }
+
+    #[test]
+    fn entry_opens_nested_menu() {
+        let nested = vec![entry(
+            "Lock",
+            ButtonAction::Command("loginctl lock-session".into()),
+        )];
+        let mut widget = ButtonWidget::new(ButtonConfig {
+            on_click: Some(ButtonAction::Popup(vec![entry(
+                "Session",
+                ButtonAction::Popup(nested.clone()),
+            )])),
+            ..Default::default()
+        });
+
+        assert!(widget.update(ButtonWidgetEvent::Click).is_none());
+        assert_eq!(widget.menu.as_ref().map(Vec::len), Some(1));
+
+        assert!(widget.update(ButtonWidgetEvent::Entry(0)).is_none());
+        assert_eq!(widget.menu, Some(nested));
+
+        assert!(widget.update(ButtonWidgetEvent::Click).is_none());
+        assert_eq!(widget.menu, None);
+    }
+}
@@ -1,4 +1,6 @@
-use chrono::{DateTime, Local};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Timelike};
 use derive_more::derive::Display;
 use iced::{
     widget::{button, text},
@@ -7,20 +9,56 @@ use iced::{
 use serde::{Deserialize, Serialize};
 use smart_default::SmartDefault;
 
-use crate::app::AppMsg;
+use super::{Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput};
+
+/// Ticks every second
+#[derive(Debug)]
+pub struct Clock;
+
+impl Module for Clock {
+    type Config = ClockFormat;
+    type Event = ClockEvent;
+    type Output = DateTime<Local>;
+
+    type Widget = ClockWidget;
+
+    fn new(_config: Self::Config, _context: ModuleContext) -> miette::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        Ok(Local::now())
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        // Wake up right after the next full second, leap seconds report more than 999ms
+        let next_second = 1000 - (Local::now().nanosecond() / 1_000_000).min(999);
+        tokio::time::sleep(Duration::from_millis(next_second as u64)).await;
 
-use super::TModule;
+        Ok(ClockEvent::Tick)
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            ClockEvent::Tick => Ok(Some(Local::now())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockEvent {
+    Tick,
+}
 
 #[derive(Debug, SmartDefault)]
-pub struct Clock {
+pub struct ClockWidget {
     #[default(Local::now())]
     pub time: DateTime<Local>,
     format: ClockFormat,
 }
 
-impl TModule for Clock {
-    type Config = ClockFormat;
-    type Event = ClockEvent;
+impl ModuleWidget<Clock> for ClockWidget {
+    type Event = ClockWidgetEvent;
 
     fn new(format: ClockFormat) -> Self {
         Self {
@@ -29,13 +67,14 @@ impl TModule for Clock {
         }
     }
 
-    fn update(&mut self, event: Self::Event) -> Option<AppMsg> {
+    fn set(&mut self, time: DateTime<Local>) {
+        self.time = time;
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Clock>> {
         match event {
-            ClockEvent::SwitchFormat => self.format.switch(),
-            ClockEvent::SetTime(time) => {
-                self.time = time;
-                return Some(AppMsg::wait_ms_msg(300, AppMsg::UpdateTime));
-            }
+            ClockWidgetEvent::SwitchFormat => self.format.switch(),
+            ClockWidgetEvent::SetFormat(format) => self.format = format,
         }
 
         None
@@ -45,15 +84,26 @@ impl TModule for Clock {
         button(text(
             self.time.format(self.format.chrono_format()).to_string(),
         ))
-        .on_press(ClockEvent::SwitchFormat)
+        .on_press(ClockWidgetEvent::SwitchFormat)
         .into()
     }
+
+    fn tooltip(&self) -> Option<String> {
+        Some(self.time.format("%A, %-d %B %Y, %H:%M:%S %Z").to_string())
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::json!({
+            "time": self.time.format(self.format.chrono_format()).to_string(),
+            "format": self.format,
+        })
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ClockEvent {
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ClockWidgetEvent {
     SwitchFormat,
-    SetTime(DateTime<Local>),
+    SetFormat(ClockFormat),
 }
 
 #[allow(non_camel_case_types)]
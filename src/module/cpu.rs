@@ -0,0 +1,294 @@
+use std::{collections::VecDeque, time::Duration};
+
+use derive_more::derive::Display;
+use iced::{
+    widget::{rich_text, span},
+    Element, Renderer, Theme,
+};
+use iced_fonts::{nerd, Nerd};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use sysinfo::{CpuRefreshKind, Process, ProcessRefreshKind, System};
+use tokio::time::Instant;
+
+use super::{Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Eighths of a character cell, for the bars and the sparkline
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Samples the CPU usage periodically, keeping the same `System` around,
+/// since the usage is measured between two refreshes of it
+pub struct Cpu {
+    config: CpuConfig,
+    system: System,
+    interval: Duration,
+    next: Instant,
+}
+
+impl Cpu {
+    async fn read(&mut self) -> miette::Result<CpuData> {
+        let processes = self.config.top_processes;
+
+        read_blocking(&mut self.system, move |system| {
+            system.refresh_cpu_specifics(CpuRefreshKind::everything());
+            system.refresh_processes_specifics(ProcessRefreshKind::new().with_cpu());
+
+            let cpus = system.cpus();
+            let cores = cpus.iter().map(|cpu| cpu.cpu_usage()).collect_vec();
+            let frequency = match cpus.len() {
+                0 => 0,
+                count => cpus.iter().map(|cpu| cpu.frequency()).sum::<u64>() / count as u64,
+            };
+
+            // Process usage is relative to a single core
+            let count = cpus.len().max(1) as f32;
+            let top = top_processes(system, processes, |process| process.cpu_usage() / count);
+
+            CpuData {
+                usage: system.global_cpu_info().cpu_usage(),
+                cores,
+                frequency,
+                top,
+            }
+        })
+        .await
+    }
+}
+
+impl Module for Cpu {
+    type Config = CpuConfig;
+    type Event = CpuEvent;
+    type Output = CpuData;
+
+    type Widget = CpuWidget;
+
+    fn new(config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        let interval = config
+            .interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| context.retry.refresh_interval(REFRESH_INTERVAL))
+            .max(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+
+        Ok(Self {
+            config,
+            system: System::new(),
+            interval,
+            next: Instant::now() + interval,
+        })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        // The first refresh only sets the baseline to measure the usage against
+        self.read().await?;
+        tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+
+        self.read().await
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        // Sleeping until a fixed deadline keeps this cancel safe
+        tokio::time::sleep_until(self.next).await;
+        self.next = Instant::now() + self.interval;
+
+        Ok(CpuEvent::Refresh)
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            CpuEvent::Refresh => self.read().await.map(Some),
+        }
+    }
+}
+
+/// Runs `read` on the system in the blocking thread pool, since going through
+/// every process in `/proc` takes long enough to hold up the other modules
+pub async fn read_blocking<T>(
+    system: &mut System,
+    read: impl FnOnce(&mut System) -> T + Send + 'static,
+) -> miette::Result<T>
+where
+    T: Send + 'static,
+{
+    let mut moved = std::mem::take(system);
+
+    let (moved, output) = tokio::task::spawn_blocking(move || {
+        let output = read(&mut moved);
+        (moved, output)
+    })
+    .await
+    .into_diagnostic()
+    .wrap_err("Failed to read the system stats")?;
+
+    *system = moved;
+    Ok(output)
+}
+
+/// The processes using the most of something, along with how much they use,
+/// grouped by name so multi-process applications (browsers) show up as one
+pub fn top_processes<T>(
+    system: &System,
+    count: usize,
+    usage: impl Fn(&Process) -> T,
+) -> Vec<(String, T)>
+where
+    T: std::iter::Sum + PartialOrd + Copy,
+{
+    system
+        .processes()
+        .values()
+        // Threads show up as processes of their own on Linux
+        .filter(|process| process.thread_kind().is_none())
+        .into_group_map_by(|process| process.name().to_string())
+        .into_iter()
+        .map(|(name, processes)| {
+            let usage = processes.into_iter().map(&usage).sum::<T>();
+            (name, usage)
+        })
+        .sorted_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal))
+        .take(count)
+        .collect()
+}
+
+/// A character high enough for the fraction (0 to 1) of a cell
+pub fn block(fraction: f32) -> char {
+    let i = (fraction.clamp(0.0, 1.0) * (BLOCKS.len() - 1) as f32).round() as usize;
+    BLOCKS[i]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuEvent {
+    Refresh,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuData {
+    /// Total usage, in percent
+    pub usage: f32,
+    /// Usage of every core, in percent
+    pub cores: Vec<f32>,
+    /// Average frequency of the cores, in MHz
+    pub frequency: u64,
+    /// Processes using the most CPU time, with their share of the total
+    pub top: Vec<(String, f32)>,
+}
+
+#[derive(Debug)]
+pub struct CpuWidget {
+    config: CpuConfig,
+    data: CpuData,
+    /// Total usage of the last samples, for the sparkline
+    history: VecDeque<f32>,
+}
+
+impl ModuleWidget<Cpu> for CpuWidget {
+    type Event = CpuWidgetEvent;
+
+    fn new(config: CpuConfig) -> Self {
+        Self {
+            history: VecDeque::with_capacity(config.history),
+            config,
+            data: CpuData::default(),
+        }
+    }
+
+    fn set(&mut self, data: CpuData) {
+        if self.history.len() >= self.config.history {
+            self.history.pop_front();
+        }
+        self.history.push_back(data.usage);
+
+        self.data = data;
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Cpu>> {
+        match event {}
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let usage = match self.config.display {
+            CpuDisplay::Usage => format!(" {:.0}%", self.data.usage),
+            CpuDisplay::Cores => {
+                let bars = self.data.cores.iter().map(|usage| block(usage / 100.0));
+                format!(" {}", bars.collect::<String>())
+            }
+            CpuDisplay::Sparkline => {
+                let bars = self.history.iter().map(|usage| block(usage / 100.0));
+                format!(" {} {:.0}%", bars.collect::<String>(), self.data.usage)
+            }
+        };
+
+        let frequency = self
+            .config
+            .show_frequency
+            .then(|| format!(" {:.1}GHz", self.data.frequency as f32 / 1000.0));
+
+        rich_text![
+            span(nerd::icon_to_string(Nerd::Cpu)).font(iced_fonts::NERD_FONT),
+            span(usage),
+            span(frequency.unwrap_or_default())
+        ]
+        .into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let CpuData {
+            usage,
+            cores,
+            frequency,
+            top,
+        } = &self.data;
+
+        let header = format!(
+            "CPU: {usage:.0}% on {} cores at {frequency}MHz",
+            cores.len()
+        );
+        let processes = top
+            .iter()
+            .map(|(name, usage)| format!("{usage:>5.1}%  {name}"));
+
+        Some([header].into_iter().chain(processes).join("\n"))
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::json!({
+            "usage": self.data.usage,
+            "cores": self.data.cores,
+            "frequency": self.data.frequency,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum CpuWidgetEvent {}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Cpu({display:?})")]
+#[serde(default)]
+pub struct CpuConfig {
+    pub display: CpuDisplay,
+    /// Shows the average frequency of the cores next to the usage
+    pub show_frequency: bool,
+    /// Time between samples, the refresh interval of the retry policy when unset
+    pub interval_ms: Option<u64>,
+    /// Samples the sparkline covers
+    #[default = 10]
+    pub history: usize,
+    /// Processes listed in the tooltip
+    #[default = 5]
+    pub top_processes: usize,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CpuDisplay {
+    /// Total usage in percent
+    #[default]
+    Usage,
+    /// A bar per core
+    Cores,
+    /// Recent total usage as a sparkline, followed by the current one
+    Sparkline,
+}
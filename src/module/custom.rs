@@ -0,0 +1,459 @@
+use std::{process::Stdio, time::Duration};
+
+use derive_more::derive::Display;
+use iced::{
+    widget::{mouse_area, text},
+    Element, Renderer, Theme,
+};
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader, Lines},
+    process::{Child, ChildStdout},
+    time::Instant,
+};
+
+use crate::util::{shell_command, spawn_command};
+
+use super::{
+    audio::ScrollDirection, Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput,
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shows the output of a user command, either re-running it periodically
+/// or reading every line a long-running one prints
+#[derive(Debug)]
+pub struct Custom {
+    config: CustomConfig,
+    source: CustomSource,
+}
+
+#[derive(Debug)]
+enum CustomSource {
+    Interval {
+        interval: Duration,
+        next: Instant,
+    },
+    Stream {
+        _child: Child,
+        lines: Lines<BufReader<ChildStdout>>,
+    },
+}
+
+impl Custom {
+    /// Runs the command to completion, for the interval mode.
+    /// A command that hangs gets killed after the timeout, failing the module
+    async fn run(config: &CustomConfig) -> miette::Result<CustomOutput> {
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let output = shell_command(&config.command).kill_on_drop(true).output();
+
+        let output = tokio::time::timeout(timeout, output)
+            .await
+            .map_err(|_| miette::miette!("`{}` didn't finish within {timeout:?}", config.command))?
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to run `{}`", config.command))?;
+
+        if !output.status.success() {
+            return Err(miette::miette!(
+                "`{}` exited with {}: {}",
+                config.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        CustomOutput::parse(&String::from_utf8_lossy(&output.stdout), config.output)
+    }
+
+    /// Next line of the long-running command, erroring once it exits
+    async fn next_line(lines: &mut Lines<BufReader<ChildStdout>>) -> miette::Result<String> {
+        lines
+            .next_line()
+            .await
+            .into_diagnostic()?
+            .ok_or_else(|| miette::miette!("The command exited"))
+    }
+}
+
+impl Module for Custom {
+    type Config = CustomConfig;
+    type Event = CustomEvent;
+    type Output = CustomOutput;
+
+    type Widget = CustomWidget;
+
+    fn new(config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        if config.command.trim().is_empty() {
+            return Err(miette::miette!("No command configured"));
+        }
+
+        let source = match config.mode {
+            CustomMode::Interval => {
+                let interval = config
+                    .interval_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or_else(|| context.retry.refresh_interval(REFRESH_INTERVAL));
+
+                CustomSource::Interval {
+                    interval,
+                    next: Instant::now() + interval,
+                }
+            }
+            CustomMode::Stream => {
+                let mut child = shell_command(&config.command)
+                    .stdout(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Failed to run `{}`", config.command))?;
+
+                let stdout = child
+                    .stdout
+                    .take()
+                    .ok_or_else(|| miette::miette!("The command has no stdout"))?;
+
+                CustomSource::Stream {
+                    _child: child,
+                    lines: BufReader::new(stdout).lines(),
+                }
+            }
+        };
+
+        Ok(Self { config, source })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        match &mut self.source {
+            CustomSource::Interval { .. } => Self::run(&self.config).await,
+            CustomSource::Stream { lines, .. } => {
+                CustomOutput::parse(&Self::next_line(lines).await?, self.config.output)
+            }
+        }
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        match &mut self.source {
+            CustomSource::Interval { interval, next } => {
+                // Sleeping until a fixed deadline keeps this cancel safe
+                tokio::time::sleep_until(*next).await;
+                *next = Instant::now() + *interval;
+
+                Ok(CustomEvent::Refresh)
+            }
+            CustomSource::Stream { lines, .. } => {
+                Self::next_line(lines).await.map(CustomEvent::Line)
+            }
+        }
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            CustomEvent::Refresh => Self::run(&self.config).await.map(Some),
+            CustomEvent::Line(line) => CustomOutput::parse(&line, self.config.output).map(Some),
+            CustomEvent::Action(action) => {
+                if let Some(command) = self.config.action(action) {
+                    spawn_command(command);
+                }
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustomEvent {
+    Refresh,
+    Line(String),
+    Action(CustomWidgetEvent),
+}
+
+/// What the command printed, either parsed from the plain text lines (text, tooltip, class)
+/// or from the waybar JSON format
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CustomOutput {
+    pub text: String,
+    pub tooltip: Option<String>,
+    #[serde(deserialize_with = "classes")]
+    pub class: Vec<String>,
+    #[serde(deserialize_with = "percentage")]
+    pub percentage: Option<u8>,
+}
+
+impl CustomOutput {
+    fn parse(output: &str, format: CustomOutputFormat) -> miette::Result<Self> {
+        match format {
+            CustomOutputFormat::Text => {
+                let mut lines = output.lines();
+                let mut line = || {
+                    lines
+                        .next()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(ToString::to_string)
+                };
+
+                Ok(Self {
+                    text: line().unwrap_or_default(),
+                    tooltip: line(),
+                    class: line().into_iter().collect(),
+                    percentage: None,
+                })
+            }
+            CustomOutputFormat::Json => serde_json::from_str(output.trim())
+                .into_diagnostic()
+                .wrap_err("The command didn't print valid JSON"),
+        }
+    }
+
+    /// Critical and urgent are the classes waybar scripts use for the alarming states
+    fn critical(&self) -> bool {
+        self.class
+            .iter()
+            .any(|class| matches!(class.as_str(), "critical" | "urgent"))
+    }
+}
+
+/// Waybar allows the class to be a single string as well as a list of them, or null
+fn classes<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Classes {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Option::<Classes>::deserialize(deserializer)? {
+        Some(Classes::One(class)) => vec![class],
+        Some(Classes::Many(classes)) => classes,
+        None => vec![],
+    })
+}
+
+/// Scripts don't always keep the percentage in range, so it's clamped rather than rejected
+fn percentage<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let percentage = Option::<i64>::deserialize(deserializer)?;
+    Ok(percentage.map(|percentage| percentage.clamp(0, 100) as u8))
+}
+
+#[derive(Debug)]
+pub struct CustomWidget {
+    config: CustomConfig,
+    output: CustomOutput,
+}
+
+impl ModuleWidget<Custom> for CustomWidget {
+    type Event = CustomWidgetEvent;
+
+    fn new(config: CustomConfig) -> Self {
+        Self {
+            config,
+            output: Default::default(),
+        }
+    }
+
+    fn set(&mut self, output: CustomOutput) {
+        self.output = output;
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Custom>> {
+        self.config
+            .action(event)
+            .is_some()
+            .then(|| ModuleWidgetUpdateOutput::module(CustomEvent::Action(event)))
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let label = match (self.output.text.is_empty(), self.output.percentage) {
+            (true, Some(percentage)) => format!("{percentage}%"),
+            _ => self.output.text.clone(),
+        };
+
+        let label = match self.output.critical() {
+            true => text(label).style(text::danger),
+            false => text(label),
+        };
+
+        mouse_area(label)
+            .on_press(CustomWidgetEvent::Click)
+            .on_middle_press(CustomWidgetEvent::MiddleClick)
+            .on_right_press(CustomWidgetEvent::RightClick)
+            .on_scroll(|delta| CustomWidgetEvent::Scroll(ScrollDirection::from_delta(delta)))
+            .into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        self.output.tooltip.clone()
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::json!({
+            "command": self.config.command,
+            "text": self.output.text,
+            "tooltip": self.output.tooltip,
+            "class": self.output.class,
+            "percentage": self.output.percentage,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum CustomWidgetEvent {
+    Click,
+    MiddleClick,
+    RightClick,
+    Scroll(ScrollDirection),
+}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Custom({command})")]
+#[serde(default)]
+pub struct CustomConfig {
+    /// Shell command (run with `sh -c`) whose output gets shown
+    pub command: String,
+    pub mode: CustomMode,
+    /// Time between runs in the interval mode, the refresh interval of the retry policy when unset
+    pub interval_ms: Option<u64>,
+    /// Time a run gets to finish in the interval mode, before the command is killed
+    #[default = 10_000]
+    pub timeout_ms: u64,
+    pub output: CustomOutputFormat,
+    /// Commands to run on clicks and scrolling, in the background
+    pub on_click: Option<String>,
+    pub on_middle_click: Option<String>,
+    pub on_right_click: Option<String>,
+    pub on_scroll_up: Option<String>,
+    pub on_scroll_down: Option<String>,
+}
+
+impl CustomConfig {
+    fn action(&self, event: CustomWidgetEvent) -> Option<&str> {
+        match event {
+            CustomWidgetEvent::Click => &self.on_click,
+            CustomWidgetEvent::MiddleClick => &self.on_middle_click,
+            CustomWidgetEvent::RightClick => &self.on_right_click,
+            CustomWidgetEvent::Scroll(ScrollDirection::Up) => &self.on_scroll_up,
+            CustomWidgetEvent::Scroll(ScrollDirection::Down) => &self.on_scroll_down,
+        }
+        .as_deref()
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CustomMode {
+    /// Re-runs the command periodically, showing what it printed
+    #[default]
+    Interval,
+    /// Keeps the command running, every line it prints replaces the output
+    Stream,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CustomOutputFormat {
+    /// Up to three lines: the text, the tooltip and the class
+    #[default]
+    Text,
+    /// Waybar style JSON with `text`, `tooltip`, `class` and `percentage`,
+    /// a single line of it per update in the stream mode
+    Json,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_lines() {
+        let output = CustomOutput::parse("  42%\nDisk usage\ncritical\n", CustomOutputFormat::Text);
+
+        assert_eq!(
+            output.unwrap(),
+            CustomOutput {
+                text: "42%".into(),
+                tooltip: Some("Disk usage".into()),
+                class: vec!["critical".into()],
+                percentage: None,
+            }
+        );
+    }
+
+    #[test]
+    fn text_skips_empty_lines() {
+        let output = CustomOutput::parse("42%\n\n", CustomOutputFormat::Text).unwrap();
+
+        assert_eq!(output.text, "42%");
+        assert_eq!(output.tooltip, None);
+        assert!(output.class.is_empty());
+    }
+
+    #[test]
+    fn json() {
+        let output = CustomOutput::parse(
+            r#"{"text": "42%", "tooltip": "Disk usage", "percentage": 42}"#,
+            CustomOutputFormat::Json,
+        );
+
+        assert_eq!(
+            output.unwrap(),
+            CustomOutput {
+                text: "42%".into(),
+                tooltip: Some("Disk usage".into()),
+                class: vec![],
+                percentage: Some(42),
+            }
+        );
+    }
+
+    #[test]
+    fn json_class_as_string() {
+        let output =
+            CustomOutput::parse(r#"{"class": "urgent"}"#, CustomOutputFormat::Json).unwrap();
+
+        assert_eq!(output.class, ["urgent"]);
+        assert!(output.critical());
+    }
+
+    #[test]
+    fn json_class_as_list() {
+        let output =
+            CustomOutput::parse(r#"{"class": ["muted", "low"]}"#, CustomOutputFormat::Json)
+                .unwrap();
+
+        assert_eq!(output.class, ["muted", "low"]);
+        assert!(!output.critical());
+    }
+
+    #[test]
+    fn json_class_null() {
+        let output = CustomOutput::parse(
+            r#"{"text": "42%", "class": null}"#,
+            CustomOutputFormat::Json,
+        )
+        .unwrap();
+
+        assert!(output.class.is_empty());
+    }
+
+    #[test]
+    fn json_percentage_clamped() {
+        let parse = |json| CustomOutput::parse(json, CustomOutputFormat::Json).unwrap();
+
+        assert_eq!(parse(r#"{"percentage": 300}"#).percentage, Some(100));
+        assert_eq!(parse(r#"{"percentage": -5}"#).percentage, Some(0));
+        assert_eq!(parse(r#"{"percentage": null}"#).percentage, None);
+    }
+
+    #[test]
+    fn invalid_json() {
+        assert!(CustomOutput::parse("42%", CustomOutputFormat::Json).is_err());
+    }
+}
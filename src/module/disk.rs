@@ -0,0 +1,412 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use derive_more::derive::Display;
+use iced::{
+    widget::{mouse_area, row, text},
+    Element, Renderer, Theme,
+};
+use iced_fonts::{nerd, Nerd};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use sysinfo::Disks;
+use tokio::time::Instant;
+
+use crate::util::{human_bytes, percent, spawn_command};
+
+use super::{Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput, Severity};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Unit of the sector counts in the block device stats, whatever the actual sector size
+const SECTOR_SIZE: u64 = 512;
+
+/// Reads the space left on the configured mount points periodically,
+/// along with how fast their devices get read and written
+pub struct Disk {
+    config: DiskConfig,
+    stats: DiskStats,
+    interval: Duration,
+    next: Instant,
+}
+
+impl Disk {
+    /// Reads in the blocking thread pool, listing the mounts and reading the device stats
+    /// goes through the file system
+    async fn read(&mut self) -> miette::Result<Vec<MountData>> {
+        let mounts = self.config.mounts.clone();
+        let mut stats = std::mem::take(&mut self.stats);
+
+        let (stats, mounts) = tokio::task::spawn_blocking(move || {
+            let mounts = stats.read(&mounts);
+            (stats, mounts)
+        })
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to read the disks")?;
+
+        self.stats = stats;
+        Ok(mounts)
+    }
+}
+
+/// What the reads keep track of, moved into the blocking thread pool for each of them
+#[derive(Default)]
+struct DiskStats {
+    disks: Disks,
+    /// Bytes read and written so far by every block device, when they were last read
+    io: HashMap<String, (u64, u64, Instant)>,
+}
+
+impl DiskStats {
+    fn read(&mut self, mounts: &[DiskMount]) -> Vec<MountData> {
+        // Mounts come and go (removable drives), so the whole list gets read again
+        self.disks.refresh_list();
+
+        // Mounts of the same device (subvolumes, bind mounts) share its rates, measured once
+        // per read, since measuring them again right away would see next to no time pass
+        let mut rates = HashMap::new();
+
+        mounts
+            .iter()
+            .map(|mount| {
+                let disk = self
+                    .disks
+                    .iter()
+                    .find(|disk| disk.mount_point() == mount.path);
+
+                let Some(disk) = disk else {
+                    return MountData {
+                        path: mount.path.clone(),
+                        ..Default::default()
+                    };
+                };
+
+                let device = block_device(Path::new(disk.name()));
+                let io = match (&device, mount.io) {
+                    (Some(device), true) => *rates
+                        .entry(device.clone())
+                        .or_insert_with(|| Self::throughput(&mut self.io, device)),
+                    _ => None,
+                };
+
+                MountData {
+                    path: mount.path.clone(),
+                    device,
+                    mounted: true,
+                    total: disk.total_space(),
+                    available: disk.available_space(),
+                    io,
+                }
+            })
+            .collect()
+    }
+
+    /// Bytes per second read and written since the last read, nothing on the first one
+    fn throughput(
+        io: &mut HashMap<String, (u64, u64, Instant)>,
+        device: &str,
+    ) -> Option<(f64, f64)> {
+        let stat = std::fs::read_to_string(format!("/sys/class/block/{device}/stat"));
+        let fields = match &stat {
+            Ok(stat) => stat.split_whitespace().collect_vec(),
+            Err(err) => {
+                tracing::debug!("Failed to read the stats of {device}: {err}");
+                return None;
+            }
+        };
+
+        // Sectors read is the 3rd field, sectors written the 7th
+        let sectors = |i: usize| fields.get(i)?.parse::<u64>().ok();
+        let (read, written) = (sectors(2)? * SECTOR_SIZE, sectors(6)? * SECTOR_SIZE);
+        let now = Instant::now();
+
+        let (last_read, last_written, last) =
+            io.insert(device.to_string(), (read, written, now))?;
+        let elapsed = now.duration_since(last).as_secs_f64();
+
+        (elapsed > 0.0).then(|| {
+            (
+                read.saturating_sub(last_read) as f64 / elapsed,
+                written.saturating_sub(last_written) as f64 / elapsed,
+            )
+        })
+    }
+}
+
+/// Name of the block device under `/sys/class/block`, following links such as
+/// `/dev/mapper/root` to the device they stand for
+fn block_device(path: &Path) -> Option<String> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+/// Single quotes a path for `sh`
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+impl Module for Disk {
+    type Config = DiskConfig;
+    type Event = DiskEvent;
+    type Output = Vec<MountData>;
+
+    type Widget = DiskWidget;
+
+    fn new(config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        if config.mounts.is_empty() {
+            return Err(miette::miette!("No mount points configured"));
+        }
+
+        let interval = config
+            .interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| context.retry.refresh_interval(REFRESH_INTERVAL));
+
+        Ok(Self {
+            config,
+            stats: DiskStats::default(),
+            interval,
+            next: Instant::now() + interval,
+        })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        self.read().await
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        // Sleeping until a fixed deadline keeps this cancel safe
+        tokio::time::sleep_until(self.next).await;
+        self.next = Instant::now() + self.interval;
+
+        Ok(DiskEvent::Refresh)
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            DiskEvent::Refresh => self.read().await.map(Some),
+            DiskEvent::Open(path) => {
+                if let Some(file_manager) = &self.config.file_manager {
+                    spawn_command(&format!("{file_manager} {}", quote(&path)));
+                }
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiskEvent {
+    Refresh,
+    Open(PathBuf),
+}
+
+/// Sizes in bytes, rates in bytes per second
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MountData {
+    pub path: PathBuf,
+    pub device: Option<String>,
+    /// Something is mounted there at the moment
+    pub mounted: bool,
+    pub total: u64,
+    pub available: u64,
+    /// Read and write rates, when asked for and known
+    pub io: Option<(f64, f64)>,
+}
+
+impl MountData {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.available)
+    }
+
+    pub fn percent(&self) -> f32 {
+        percent(self.used(), self.total)
+    }
+
+    /// Fills in the placeholders of the format
+    fn render(&self, format: &str, label: &str) -> String {
+        let (read, write) = self.io.unwrap_or_default();
+
+        [
+            ("{label}", label.to_string()),
+            ("{used}", human_bytes(self.used())),
+            ("{free}", human_bytes(self.available)),
+            ("{total}", human_bytes(self.total)),
+            ("{percent}", format!("{:.0}", self.percent())),
+            ("{read}", human_bytes(read as u64)),
+            ("{write}", human_bytes(write as u64)),
+        ]
+        .into_iter()
+        .fold(format.to_string(), |text, (placeholder, value)| {
+            text.replace(placeholder, &value)
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DiskWidget {
+    config: DiskConfig,
+    mounts: Vec<MountData>,
+}
+
+impl ModuleWidget<Disk> for DiskWidget {
+    type Event = DiskWidgetEvent;
+
+    fn new(config: DiskConfig) -> Self {
+        Self {
+            config,
+            mounts: vec![],
+        }
+    }
+
+    fn set(&mut self, mounts: Vec<MountData>) {
+        self.mounts = mounts;
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Disk>> {
+        match event {
+            DiskWidgetEvent::Open(i) => {
+                self.config.file_manager.as_ref()?;
+                let mount = self.config.mounts.get(i)?;
+
+                Some(ModuleWidgetUpdateOutput::module(DiskEvent::Open(
+                    mount.path.clone(),
+                )))
+            }
+        }
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let mounts =
+            self.config
+                .mounts
+                .iter()
+                .zip(&self.mounts)
+                .enumerate()
+                .map(|(i, (mount, data))| {
+                    let label = mount.label();
+                    let (label, severity) = match data.mounted {
+                        true => (
+                            data.render(&mount.format, &label),
+                            Severity::of(
+                                data.percent(),
+                                mount.warning.map(f32::from),
+                                mount.critical.map(f32::from),
+                            ),
+                        ),
+                        false => (format!("{label} -"), Severity::Normal),
+                    };
+
+                    mouse_area(text(label).style(move |theme| severity.style(theme)))
+                        .on_press(DiskWidgetEvent::Open(i))
+                        .into()
+                });
+
+        row![text(nerd::icon_to_string(Nerd::Harddisk)).font(iced_fonts::NERD_FONT)]
+            .extend(mounts)
+            .spacing(5)
+            .into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let lines = self.mounts.iter().map(|data| {
+            let path = data.path.display();
+
+            if !data.mounted {
+                return format!("{path}: not mounted");
+            }
+
+            let device = data.device.as_deref().unwrap_or("unknown device");
+            let mut line = format!(
+                "{path} ({device}): {} of {} used ({:.0}%), {} free",
+                human_bytes(data.used()),
+                human_bytes(data.total),
+                data.percent(),
+                human_bytes(data.available)
+            );
+
+            if let Some((read, write)) = data.io {
+                line += &format!(
+                    ", reading {}/s, writing {}/s",
+                    human_bytes(read as u64),
+                    human_bytes(write as u64)
+                );
+            }
+
+            line
+        });
+
+        Some(lines.collect_vec().join("\n"))
+    }
+
+    fn state(&self) -> serde_json::Value {
+        self.mounts
+            .iter()
+            .map(|data| {
+                serde_json::json!({
+                    "path": data.path,
+                    "mounted": data.mounted,
+                    "total": data.total,
+                    "available": data.available,
+                    "read": data.io.map(|(read, _)| read),
+                    "write": data.io.map(|(_, write)| write),
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DiskWidgetEvent {
+    /// Opens a mount point in the file manager, by its index
+    Open(usize),
+}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Disk({})", mounts.iter().map(|m| m.path.display()).join(", "))]
+#[serde(default)]
+pub struct DiskConfig {
+    #[default(vec![DiskMount::default()])]
+    pub mounts: Vec<DiskMount>,
+    /// Command opening a mount point on click, which gets passed as the last argument,
+    /// e.g. `Some("nautilus")`
+    pub file_manager: Option<String>,
+    /// Time between reads, the refresh interval of the retry policy when unset
+    pub interval_ms: Option<u64>,
+}
+
+#[derive(SmartDefault, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskMount {
+    #[default(PathBuf::from("/"))]
+    pub path: PathBuf,
+    /// Name shown for the mount, the mount point when unset
+    pub label: Option<String>,
+    /// Placeholders: `{label}`, `{used}`, `{free}`, `{total}`, `{percent}`,
+    /// and `{read}` and `{write}` (per second) when `io` is set
+    #[default("{label} {percent}%".to_string())]
+    pub format: String,
+    /// Usage, in percent, from which the mount shows a warning
+    #[default(Some(80))]
+    pub warning: Option<u8>,
+    /// Usage, in percent, from which the mount shows up as critical
+    #[default(Some(95))]
+    pub critical: Option<u8>,
+    /// Keeps track of the read and write rates of the device
+    pub io: bool,
+}
+
+impl DiskMount {
+    fn label(&self) -> String {
+        self.label
+            .clone()
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+}
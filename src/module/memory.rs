@@ -0,0 +1,234 @@
+use std::time::Duration;
+
+use derive_more::derive::Display;
+use iced::{
+    widget::{row, text},
+    Element, Renderer, Theme,
+};
+use iced_fonts::{nerd, Nerd};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use sysinfo::{ProcessRefreshKind, System};
+use tokio::time::Instant;
+
+use crate::util::{human_bytes, percent};
+
+use super::{
+    cpu::{read_blocking, top_processes},
+    Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput, Severity,
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Reads the RAM and swap usage periodically
+pub struct Memory {
+    config: MemoryConfig,
+    system: System,
+    interval: Duration,
+    next: Instant,
+}
+
+impl Memory {
+    async fn read(&mut self) -> miette::Result<MemoryData> {
+        let processes = self.config.top_processes;
+
+        read_blocking(&mut self.system, move |system| {
+            system.refresh_memory();
+            system.refresh_processes_specifics(ProcessRefreshKind::new().with_memory());
+
+            MemoryData {
+                ram_used: system.used_memory(),
+                ram_total: system.total_memory(),
+                swap_used: system.used_swap(),
+                swap_total: system.total_swap(),
+                top: top_processes(system, processes, |process| process.memory()),
+            }
+        })
+        .await
+    }
+}
+
+impl Module for Memory {
+    type Config = MemoryConfig;
+    type Event = MemoryEvent;
+    type Output = MemoryData;
+
+    type Widget = MemoryWidget;
+
+    fn new(config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        let interval = config
+            .interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| context.retry.refresh_interval(REFRESH_INTERVAL));
+
+        Ok(Self {
+            config,
+            system: System::new(),
+            interval,
+            next: Instant::now() + interval,
+        })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        self.read().await
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        // Sleeping until a fixed deadline keeps this cancel safe
+        tokio::time::sleep_until(self.next).await;
+        self.next = Instant::now() + self.interval;
+
+        Ok(MemoryEvent::Refresh)
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            MemoryEvent::Refresh => self.read().await.map(Some),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryEvent {
+    Refresh,
+}
+
+/// Sizes in bytes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryData {
+    pub ram_used: u64,
+    pub ram_total: u64,
+    pub swap_used: u64,
+    pub swap_total: u64,
+    /// Processes using the most memory, with how much they use
+    pub top: Vec<(String, u64)>,
+}
+
+impl MemoryData {
+    pub fn ram_percent(&self) -> f32 {
+        percent(self.ram_used, self.ram_total)
+    }
+
+    pub fn swap_percent(&self) -> f32 {
+        percent(self.swap_used, self.swap_total)
+    }
+
+    /// Fills in the placeholders of the format
+    fn render(&self, format: &str) -> String {
+        [
+            ("{ram_used}", human_bytes(self.ram_used)),
+            ("{ram_total}", human_bytes(self.ram_total)),
+            ("{ram_percent}", format!("{:.0}", self.ram_percent())),
+            ("{swap_used}", human_bytes(self.swap_used)),
+            ("{swap_total}", human_bytes(self.swap_total)),
+            ("{swap_percent}", format!("{:.0}", self.swap_percent())),
+        ]
+        .into_iter()
+        .fold(format.to_string(), |text, (placeholder, value)| {
+            text.replace(placeholder, &value)
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryWidget {
+    config: MemoryConfig,
+    data: MemoryData,
+}
+
+impl ModuleWidget<Memory> for MemoryWidget {
+    type Event = MemoryWidgetEvent;
+
+    fn new(config: MemoryConfig) -> Self {
+        Self {
+            config,
+            data: MemoryData::default(),
+        }
+    }
+
+    fn set(&mut self, data: MemoryData) {
+        self.data = data;
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Memory>> {
+        match event {}
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let MemoryConfig {
+            format,
+            warning,
+            critical,
+            ..
+        } = &self.config;
+
+        let severity = Severity::of(
+            self.data.ram_percent(),
+            warning.map(f32::from),
+            critical.map(f32::from),
+        );
+
+        row![
+            text(nerd::icon_to_string(Nerd::Memory))
+                .font(iced_fonts::NERD_FONT)
+                .style(move |theme| severity.style(theme)),
+            text(self.data.render(format)).style(move |theme| severity.style(theme))
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let data = &self.data;
+
+        let header = format!(
+            "RAM: {} / {} ({:.0}%)\nSwap: {} / {} ({:.0}%)",
+            human_bytes(data.ram_used),
+            human_bytes(data.ram_total),
+            data.ram_percent(),
+            human_bytes(data.swap_used),
+            human_bytes(data.swap_total),
+            data.swap_percent(),
+        );
+        let processes = data
+            .top
+            .iter()
+            .map(|(name, memory)| format!("{:>9}  {name}", human_bytes(*memory)));
+
+        Some([header].into_iter().chain(processes).join("\n"))
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::json!({
+            "ram_used": self.data.ram_used,
+            "ram_total": self.data.ram_total,
+            "swap_used": self.data.swap_used,
+            "swap_total": self.data.swap_total,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum MemoryWidgetEvent {}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Memory({format})")]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Text next to the icon. Placeholders: `{ram_used}`, `{ram_total}`, `{ram_percent}`,
+    /// `{swap_used}`, `{swap_total}` and `{swap_percent}`, sizes being human readable
+    #[default("{ram_percent}%".to_string())]
+    pub format: String,
+    /// RAM usage, in percent, from which the module shows a warning
+    #[default(Some(80))]
+    pub warning: Option<u8>,
+    /// RAM usage, in percent, from which the module shows up as critical
+    #[default(Some(95))]
+    pub critical: Option<u8>,
+    /// Time between reads, the refresh interval of the retry policy when unset
+    pub interval_ms: Option<u64>,
+    /// Processes listed in the tooltip
+    #[default = 5]
+    pub top_processes: usize,
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use derive_more::derive::Display;
+use iced::{widget::button, Element, Renderer, Theme};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+
+use super::{
+    audio::{
+        AudioCommand, AudioDeviceKind, AudioDevices, AudioDevicesEvent, AudioOutput, AudioSource,
+        ScrollDirection,
+    },
+    Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput,
+};
+
+pub struct Microphone(AudioSource);
+
+impl Module for Microphone {
+    type Config = MicrophoneConfig;
+    type Event = MicrophoneEvent;
+    type Output = AudioOutput;
+
+    type Widget = MicrophoneWidget;
+
+    fn new(_config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        Ok(Self(AudioSource::new(context)))
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        self.0.init().await
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        self.0.next().await.map(MicrophoneEvent::Backend)
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            MicrophoneEvent::Backend(output) => Ok(Some(output)),
+            MicrophoneEvent::Command(command) => {
+                self.0.send(command);
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MicrophoneEvent {
+    Backend(AudioOutput),
+    Command(AudioCommand),
+}
+
+#[derive(Debug)]
+pub struct MicrophoneWidget {
+    config: MicrophoneConfig,
+    devices: AudioDevices,
+}
+
+impl ModuleWidget<Microphone> for MicrophoneWidget {
+    type Event = MicrophoneWidgetEvent;
+
+    fn new(config: MicrophoneConfig) -> Self {
+        Self {
+            config,
+            devices: AudioDevices::new(AudioDeviceKind::Source),
+        }
+    }
+
+    fn set(&mut self, output: AudioOutput) {
+        match output {
+            AudioOutput::Data(info) => {
+                let show_monitors = self.config.show_monitors;
+                self.devices
+                    .set(&info, |d| show_monitors || !d.is_monitor());
+
+                tracing::debug!("{self:#?}");
+            }
+            AudioOutput::Disconnected => self.devices.disconnect(),
+        }
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Microphone>> {
+        let MicrophoneConfig {
+            move_streams,
+            volume_step,
+            ..
+        } = &self.config;
+
+        match event {
+            MicrophoneWidgetEvent::CycleDefault => self.devices.cycle_default(*move_streams),
+            MicrophoneWidgetEvent::ToggleMute => self.devices.toggle_mute(),
+            MicrophoneWidgetEvent::Scroll(direction) => {
+                self.devices.change_volume(direction, *volume_step)
+            }
+        }
+        .map(|command| ModuleWidgetUpdateOutput::module(MicrophoneEvent::Command(command)))
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let muted = self.devices.default_data().map(|d| d.muted());
+        let warn = match (self.config.warn, muted) {
+            (MicrophoneWarning::Muted, Some(true)) => true,
+            (MicrophoneWarning::Live, Some(false)) => true,
+            _ => false,
+        };
+
+        self.devices
+            .view(&self.config.aliases, move |theme, status| match warn {
+                true => button::danger(theme, status),
+                false => button::secondary(theme, status),
+            })
+            .map(|ev| match ev {
+                AudioDevicesEvent::CycleDefault => MicrophoneWidgetEvent::CycleDefault,
+                AudioDevicesEvent::ToggleMute => MicrophoneWidgetEvent::ToggleMute,
+                AudioDevicesEvent::Scroll(direction) => MicrophoneWidgetEvent::Scroll(direction),
+            })
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        self.devices.tooltip()
+    }
+
+    fn state(&self) -> serde_json::Value {
+        self.devices.state(&self.config.aliases)
+    }
+}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Microphone")]
+#[serde(default)]
+pub struct MicrophoneConfig {
+    /// Show the monitor sources of the sinks alongside the actual inputs
+    pub show_monitors: bool,
+    /// Move the currently recording streams over to the new default source when switching
+    pub move_streams: bool,
+    /// Volume change (in percent) per scroll step
+    #[default = 5]
+    pub volume_step: u8,
+    /// Which microphone state gets highlighted with the warning style
+    pub warn: MicrophoneWarning,
+    /// Display names to show instead of the source descriptions,
+    /// keyed either by the source name or by its description
+    pub aliases: BTreeMap<String, String>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MicrophoneWarning {
+    /// Highlight the microphone while it's muted, so you don't talk into the void
+    #[default]
+    Muted,
+    /// Highlight the microphone while it's live
+    Live,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum MicrophoneWidgetEvent {
+    CycleDefault,
+    ToggleMute,
+    Scroll(ScrollDirection),
+}
@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use derive_more::derive::Display;
+use iced::{
+    futures::{stream::BoxStream, StreamExt},
+    widget::{row, text},
+    Element, Renderer, Theme,
+};
+use iced_fonts::{nerd, Nerd};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+
+use super::{
+    audio::{pipewire, AudioBackend, AudioOutput, AudioSource, VideoCaptureKind, VideoStream},
+    Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput,
+};
+
+/// Watches the audio server and, when that can't see the video graph, PipeWire
+/// for apps recording the microphone, the camera or the screen
+pub struct Privacy {
+    audio: AudioSource,
+    video: Option<BoxStream<'static, Arc<Vec<VideoStream>>>>,
+}
+
+impl Module for Privacy {
+    type Config = PrivacyConfig;
+    type Event = PrivacyEvent;
+    type Output = PrivacyOutput;
+
+    type Widget = PrivacyWidget;
+
+    fn new(_config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        // Without PipeWire there are no video streams to watch, and retrying would only
+        // keep failing
+        let video = match context.audio_backend {
+            AudioBackend::PipeWire => None,
+            _ if pipewire::running() => Some(pipewire::video(context.retry).boxed()),
+            _ => {
+                tracing::info!("PipeWire isn't running, only the audio server gets watched");
+                None
+            }
+        };
+
+        Ok(Self {
+            audio: AudioSource::new(context),
+            video,
+        })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        self.audio.init().await.map(PrivacyOutput::Audio)
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        let Some(video) = &mut self.video else {
+            return self.audio.next().await.map(PrivacyEvent::Audio);
+        };
+
+        tokio::select! {
+            event = self.audio.next() => event.map(PrivacyEvent::Audio),
+            streams = video.next() => streams
+                .map(PrivacyEvent::Video)
+                .ok_or_else(|| miette::miette!("The PipeWire video watcher stopped")),
+        }
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            PrivacyEvent::Audio(output) => Ok(Some(PrivacyOutput::Audio(output))),
+            PrivacyEvent::Video(streams) => Ok(Some(PrivacyOutput::Video(streams))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PrivacyEvent {
+    Audio(AudioOutput),
+    Video(Arc<Vec<VideoStream>>),
+}
+
+#[derive(Debug, Clone)]
+pub enum PrivacyOutput {
+    Audio(AudioOutput),
+    Video(Arc<Vec<VideoStream>>),
+}
+
+/// Shows which apps are currently recording the microphone, the camera or the screen
+#[derive(Debug)]
+pub struct PrivacyWidget {
+    config: PrivacyConfig,
+
+    microphone: Vec<String>,
+    camera: Vec<String>,
+    screen: Vec<String>,
+}
+
+impl PrivacyWidget {
+    fn filtered<'a>(&self, apps: impl Iterator<Item = &'a str>) -> Vec<String> {
+        apps.filter(|app| !self.config.ignore.iter().any(|ignored| ignored == app))
+            .map(ToString::to_string)
+            .unique()
+            .sorted()
+            .collect()
+    }
+
+    fn set_video(&mut self, streams: &[VideoStream]) {
+        let apps = |kind| {
+            streams
+                .iter()
+                .filter(move |stream| stream.kind() == kind)
+                .map(VideoStream::app_name)
+        };
+
+        self.camera = self.filtered(apps(VideoCaptureKind::Camera));
+        self.screen = self.filtered(apps(VideoCaptureKind::Screen));
+    }
+}
+
+impl ModuleWidget<Privacy> for PrivacyWidget {
+    type Event = PrivacyWidgetEvent;
+
+    fn new(config: PrivacyConfig) -> Self {
+        Self {
+            config,
+
+            microphone: vec![],
+            camera: vec![],
+            screen: vec![],
+        }
+    }
+
+    fn set(&mut self, output: PrivacyOutput) {
+        match output {
+            PrivacyOutput::Audio(AudioOutput::Data(info)) => {
+                self.microphone = self.filtered(info.recording_apps(self.config.ignore_monitors));
+
+                // Backends that can't see the video graph leave it to the dedicated watcher
+                if let Some(streams) = info.video_streams() {
+                    self.set_video(streams);
+                }
+            }
+            PrivacyOutput::Audio(AudioOutput::Disconnected) => self.microphone.clear(),
+            PrivacyOutput::Video(streams) => self.set_video(&streams),
+        }
+
+        tracing::debug!("{self:#?}");
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Privacy>> {
+        match event {}
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let indicators = [
+            (Nerd::Microphone, &self.microphone),
+            (Nerd::Webcam, &self.camera),
+            (Nerd::Monitor, &self.screen),
+        ]
+        .into_iter()
+        .filter(|(_, apps)| !apps.is_empty())
+        .map(|(icon, _)| {
+            text(nerd::icon_to_string(icon))
+                .font(iced_fonts::NERD_FONT)
+                .style(text::danger)
+                .into()
+        });
+
+        row(indicators).spacing(5).into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let lines = [
+            ("Microphone", &self.microphone),
+            ("Camera", &self.camera),
+            ("Screen", &self.screen),
+        ]
+        .into_iter()
+        .filter(|(_, apps)| !apps.is_empty())
+        .map(|(label, apps)| format!("{label}: {}", apps.join(", ")))
+        .collect_vec();
+
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::json!({
+            "microphone": self.microphone,
+            "camera": self.camera,
+            "screen": self.screen,
+        })
+    }
+}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Privacy")]
+#[serde(default)]
+pub struct PrivacyConfig {
+    /// Don't count apps that only record a sink monitor (peak meters, desktop audio capture)
+    #[default = true]
+    pub ignore_monitors: bool,
+    /// App names that never trigger the indicator
+    pub ignore: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum PrivacyWidgetEvent {}
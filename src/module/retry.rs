@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+
+/// How a data source retries after failing, and how often polled sources refresh
+#[derive(SmartDefault, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Delay before the first retry
+    #[default = 500]
+    pub min_delay_ms: u64,
+    /// Cap for the delay, which doubles with every failure in a row
+    #[default = 60_000]
+    pub max_delay_ms: u64,
+    /// Random extra delay, in percent of the delay, so sources failing together
+    /// don't all retry at the same time
+    #[default = 20]
+    pub jitter: u8,
+    /// Restarts of a failed module before it gives up for good, unlimited when unset.
+    /// Sources reconnecting on their own (the audio backends) only use the delays,
+    /// they show a disconnected state meanwhile
+    pub max_attempts: Option<u32>,
+    /// Time between refreshes of sources that get polled, the module picks one when unset
+    pub refresh_ms: Option<u64>,
+}
+
+impl RetryPolicy {
+    pub fn refresh_interval(&self, default: Duration) -> Duration {
+        self.refresh_ms
+            .map(Duration::from_millis)
+            .unwrap_or(default)
+    }
+
+    /// Delay after `failures` failures in a row, jitter included
+    fn delay(&self, failures: u32) -> Duration {
+        let max_delay = self.max_delay_ms.max(self.min_delay_ms);
+        let delay = self
+            .min_delay_ms
+            .saturating_mul(1 << failures.saturating_sub(1).min(32))
+            .min(max_delay);
+
+        let jitter = delay.saturating_mul(self.jitter.min(100) as u64) / 100;
+
+        Duration::from_millis(delay + fastrand::u64(0..=jitter))
+    }
+}
+
+/// Failures of a data source in a row, and the delays before retrying it
+#[derive(Debug)]
+pub struct Backoff {
+    policy: RetryPolicy,
+    failures: u32,
+}
+
+impl Backoff {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            failures: 0,
+        }
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// The source works again
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Counts a failure, returning how long to wait before retrying,
+    /// or nothing once the policy gives up
+    pub fn fail(&mut self) -> Option<Duration> {
+        self.failures = self.failures.saturating_add(1);
+
+        match self.policy.max_attempts {
+            Some(max_attempts) if self.failures > max_attempts => None,
+            _ => Some(self.policy.delay(self.failures)),
+        }
+    }
+
+    /// Same as [`Backoff::fail`], but never gives up
+    pub fn fail_forever(&mut self) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        self.policy.delay(self.failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min_delay_ms: u64, max_delay_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            min_delay_ms,
+            max_delay_ms,
+            jitter: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn delay_doubles_with_every_failure() {
+        let policy = policy(100, 10_000);
+
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn delay_stops_at_max() {
+        let policy = policy(100, 10_000);
+
+        assert_eq!(policy.delay(8), Duration::from_millis(10_000));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(10_000));
+    }
+
+    #[test]
+    fn min_above_max_wins() {
+        let policy = policy(1_000, 10);
+
+        assert_eq!(policy.delay(1), Duration::from_millis(1_000));
+        assert_eq!(policy.delay(5), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_share() {
+        let policy = RetryPolicy {
+            jitter: 20,
+            ..policy(1_000, 10_000)
+        };
+
+        for _ in 0..1_000 {
+            let delay = policy.delay(1);
+            assert!(
+                (Duration::from_millis(1_000)..=Duration::from_millis(1_200)).contains(&delay),
+                "{delay:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn backoff_gives_up_after_max_attempts() {
+        let mut backoff = Backoff::new(RetryPolicy {
+            max_attempts: Some(2),
+            ..policy(100, 10_000)
+        });
+
+        assert_eq!(backoff.fail(), Some(Duration::from_millis(100)));
+        assert_eq!(backoff.fail(), Some(Duration::from_millis(200)));
+        assert_eq!(backoff.fail(), None);
+        assert_eq!(backoff.failures(), 3);
+
+        backoff.reset();
+        assert_eq!(backoff.fail(), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn fail_forever_ignores_max_attempts() {
+        let mut backoff = Backoff::new(RetryPolicy {
+            max_attempts: Some(1),
+            ..policy(100, 10_000)
+        });
+
+        backoff.fail_forever();
+        assert_eq!(backoff.fail_forever(), Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,534 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use derive_more::derive::Display;
+use iced::{
+    widget::{row, text},
+    Element, Renderer, Theme,
+};
+use iced_fonts::{nerd, Nerd};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use tokio::time::Instant;
+
+use super::{Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput, Severity};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Chips of the CPU sensors, the first one found gets shown when no sensor is configured
+const CPU_CHIPS: [&str; 4] = ["coretemp", "k10temp", "zenpower", "cpu_thermal"];
+
+/// Temperature the icon fills up at, when neither the config nor the sensor has a critical one
+const FULL_SCALE: f32 = 100.0;
+
+/// Reads the hwmon temperature sensors periodically
+pub struct Temperature {
+    config: TemperatureConfig,
+    interval: Duration,
+    next: Instant,
+}
+
+impl Temperature {
+    fn read(&self) -> miette::Result<TemperatureData> {
+        let sensors = Sensor::all(&self.config.root)?;
+
+        let sensor = match &self.config.sensor {
+            Some(name) => sensors
+                .iter()
+                .find(|sensor| sensor.label == *name || sensor.name() == *name),
+            None => CPU_CHIPS
+                .iter()
+                .find_map(|chip| sensors.iter().find(|sensor| sensor.chip == *chip))
+                .or(sensors.first()),
+        };
+
+        let Some(sensor) = sensor else {
+            return Err(match &self.config.sensor {
+                Some(name) => miette::miette!(
+                    "No temperature sensor called {name}, found: {}",
+                    sensors.iter().map(Sensor::name).join(", ")
+                ),
+                None => miette::miette!("No temperature sensors in {}", self.config.root.display()),
+            });
+        };
+
+        Ok(TemperatureData {
+            sensor: sensor.name(),
+            celsius: sensor.celsius,
+            critical: sensor.critical,
+            all: sensors.iter().map(|s| (s.name(), s.celsius)).collect(),
+        })
+    }
+}
+
+/// A `temp*_input` of a hwmon chip
+#[derive(Debug)]
+struct Sensor {
+    chip: String,
+    label: String,
+    celsius: f32,
+    critical: Option<f32>,
+}
+
+impl Sensor {
+    /// Every sensor under the root, in the order of the chips and their inputs
+    fn all(root: &Path) -> miette::Result<Vec<Self>> {
+        let chips = std::fs::read_dir(root)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to list {}", root.display()))?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .sorted_by_key(|path| sort_key(path, "hwmon"));
+
+        Ok(chips.flat_map(|chip| Self::of_chip(&chip)).collect())
+    }
+
+    fn of_chip(dir: &Path) -> Vec<Self> {
+        let chip = read_trimmed(&dir.join("name")).unwrap_or_else(|| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return vec![];
+        };
+
+        entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                let prefix = name.strip_suffix("_input")?.to_string();
+                prefix.starts_with("temp").then_some(prefix)
+            })
+            .sorted_by_key(|prefix| sort_key(Path::new(prefix), "temp"))
+            .filter_map(|prefix| {
+                let millidegrees = |suffix: &str| {
+                    read_trimmed(&dir.join(format!("{prefix}_{suffix}")))?
+                        .parse::<f32>()
+                        .ok()
+                        .map(|value| value / 1000.0)
+                };
+
+                Some(Self {
+                    chip: chip.clone(),
+                    label: read_trimmed(&dir.join(format!("{prefix}_label")))
+                        .unwrap_or_else(|| prefix.clone()),
+                    celsius: millidegrees("input")?,
+                    critical: millidegrees("crit"),
+                })
+            })
+            .collect()
+    }
+
+    /// `chip: label`, telling apart sensors with the same label on different chips
+    fn name(&self) -> String {
+        format!("{}: {}", self.chip, self.label)
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+}
+
+/// Orders `hwmon10` after `hwmon9`
+fn sort_key(path: &Path, prefix: &str) -> (u32, String) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let number = name
+        .strip_prefix(prefix)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(u32::MAX);
+
+    (number, name)
+}
+
+impl Module for Temperature {
+    type Config = TemperatureConfig;
+    type Event = TemperatureEvent;
+    type Output = TemperatureData;
+
+    type Widget = TemperatureWidget;
+
+    fn new(config: Self::Config, context: ModuleContext) -> miette::Result<Self> {
+        let interval = config
+            .interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| context.retry.refresh_interval(REFRESH_INTERVAL));
+
+        Ok(Self {
+            config,
+            interval,
+            next: Instant::now() + interval,
+        })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        self.read()
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        // Sleeping until a fixed deadline keeps this cancel safe
+        tokio::time::sleep_until(self.next).await;
+        self.next = Instant::now() + self.interval;
+
+        Ok(TemperatureEvent::Refresh)
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        match event {
+            TemperatureEvent::Refresh => self.read().map(Some),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureEvent {
+    Refresh,
+}
+
+/// Temperatures in °C
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TemperatureData {
+    pub sensor: String,
+    pub celsius: f32,
+    /// Critical temperature the sensor reports
+    pub critical: Option<f32>,
+    /// Every sensor found, for the tooltip
+    pub all: Vec<(String, f32)>,
+}
+
+#[derive(Debug)]
+pub struct TemperatureWidget {
+    config: TemperatureConfig,
+    data: Option<TemperatureData>,
+}
+
+impl TemperatureWidget {
+    /// The configured critical temperature, or the one of the sensor
+    fn critical(&self) -> Option<f32> {
+        self.config
+            .critical
+            .map(f32::from)
+            .or_else(|| self.data.as_ref()?.critical)
+    }
+}
+
+impl ModuleWidget<Temperature> for TemperatureWidget {
+    type Event = TemperatureWidgetEvent;
+
+    fn new(config: TemperatureConfig) -> Self {
+        Self { config, data: None }
+    }
+
+    fn set(&mut self, data: TemperatureData) {
+        self.data = Some(data);
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Temperature>> {
+        match event {}
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let celsius = self.data.as_ref().map(|data| data.celsius);
+        let critical = self.critical();
+
+        let severity = celsius
+            .map(|celsius| Severity::of(celsius, self.config.warning.map(f32::from), critical))
+            .unwrap_or_default();
+
+        let fill = celsius.unwrap_or_default() / critical.unwrap_or(FULL_SCALE);
+        let icon = match fill {
+            ..0.25 => Nerd::ThermometerEmpty,
+            ..0.5 => Nerd::ThermometerQuarter,
+            ..0.75 => Nerd::ThermometerHalf,
+            ..0.9 => Nerd::ThermometerThreeQuarters,
+            _ => Nerd::ThermometerFull,
+        };
+
+        let label = match celsius {
+            Some(celsius) => format!("{celsius:.0}°C"),
+            None => "-".into(),
+        };
+
+        row![
+            text(nerd::icon_to_string(icon))
+                .font(iced_fonts::NERD_FONT)
+                .style(move |theme| severity.style(theme)),
+            text(label).style(move |theme| severity.style(theme))
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let data = self.data.as_ref()?;
+
+        let lines = data.all.iter().map(|(name, celsius)| {
+            let marker = match *name == data.sensor {
+                true => "•",
+                false => " ",
+            };
+
+            format!("{marker} {name}: {celsius:.0}°C")
+        });
+
+        Some(lines.collect_vec().join("\n"))
+    }
+
+    fn state(&self) -> serde_json::Value {
+        match &self.data {
+            Some(data) => serde_json::json!({
+                "sensor": data.sensor,
+                "celsius": data.celsius,
+                "critical": self.critical(),
+            }),
+            None => serde_json::Value::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum TemperatureWidgetEvent {}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Temperature({})", sensor.as_deref().unwrap_or("auto"))]
+#[serde(default)]
+pub struct TemperatureConfig {
+    /// Sensor to show, by its label (e.g. `Package id 0`, `Tctl`) or as `chip: label`,
+    /// a CPU sensor when unset
+    pub sensor: Option<String>,
+    /// Directory with the hwmon chips
+    #[default(PathBuf::from("/sys/class/hwmon"))]
+    pub root: PathBuf,
+    /// Temperature, in °C, from which the module shows a warning
+    pub warning: Option<u16>,
+    /// Temperature, in °C, from which the module shows up as critical,
+    /// the critical temperature of the sensor when unset
+    pub critical: Option<u16>,
+    /// Time between reads, the refresh interval of the retry policy when unset
+    pub interval_ms: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A `temp*` input of a fake chip, in m°C
+    struct Input {
+        n: u32,
+        millidegrees: &'static str,
+        label: Option<&'static str>,
+        critical: Option<&'static str>,
+    }
+
+    fn input(n: u32, millidegrees: &'static str) -> Input {
+        Input {
+            n,
+            millidegrees,
+            label: None,
+            critical: None,
+        }
+    }
+
+    fn chip(root: &TempDir, dir: &str, name: Option<&str>, inputs: &[Input]) {
+        let dir = root.path().join(dir);
+        fs::create_dir(&dir).unwrap();
+
+        if let Some(name) = name {
+            fs::write(dir.join("name"), format!("{name}\n")).unwrap();
+        }
+
+        for input in inputs {
+            let file = |suffix: &str| dir.join(format!("temp{}_{suffix}", input.n));
+
+            fs::write(file("input"), format!("{}\n", input.millidegrees)).unwrap();
+            if let Some(label) = input.label {
+                fs::write(file("label"), format!("{label}\n")).unwrap();
+            }
+            if let Some(critical) = input.critical {
+                fs::write(file("crit"), format!("{critical}\n")).unwrap();
+            }
+        }
+    }
+
+    fn read(root: &TempDir, sensor: Option<&str>) -> miette::Result<TemperatureData> {
+        let temperature = Temperature {
+            config: TemperatureConfig {
+                sensor: sensor.map(Into::into),
+                root: root.path().to_path_buf(),
+                ..Default::default()
+            },
+            interval: REFRESH_INTERVAL,
+            next: Instant::now(),
+        };
+
+        temperature.read()
+    }
+
+    fn names(root: &TempDir) -> Vec<String> {
+        Sensor::all(root.path())
+            .unwrap()
+            .iter()
+            .map(Sensor::name)
+            .collect()
+    }
+
+    #[test]
+    fn parses_sensors() {
+        let root = TempDir::new().unwrap();
+        chip(
+            &root,
+            "hwmon0",
+            Some("coretemp"),
+            &[
+                Input {
+                    label: Some("Package id 0"),
+                    critical: Some("100000"),
+                    ..input(1, "45000")
+                },
+                input(2, "40500"),
+                // Unreadable inputs get skipped
+                input(3, "N/A"),
+            ],
+        );
+        chip(&root, "hwmon1", None, &[input(1, "30000")]);
+
+        let sensors = Sensor::all(root.path()).unwrap();
+        let sensors = sensors
+            .iter()
+            .map(|s| (s.name(), s.celsius, s.critical))
+            .collect_vec();
+
+        assert_eq!(
+            sensors,
+            [
+                ("coretemp: Package id 0".into(), 45.0, Some(100.0)),
+                ("coretemp: temp2".into(), 40.5, None),
+                // Chips without a name go by their directory
+                ("hwmon1: temp1".into(), 30.0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn sorts_by_number() {
+        let root = TempDir::new().unwrap();
+        for (dir, name) in [("hwmon10", "ten"), ("hwmon9", "nine"), ("hwmon2", "two")] {
+            chip(
+                &root,
+                dir,
+                Some(name),
+                &[input(10, "1000"), input(2, "1000")],
+            );
+        }
+
+        assert_eq!(
+            names(&root),
+            [
+                "two: temp2",
+                "two: temp10",
+                "nine: temp2",
+                "nine: temp10",
+                "ten: temp2",
+                "ten: temp10",
+            ]
+        );
+    }
+
+    #[test]
+    fn selects_by_label_or_name() {
+        let root = TempDir::new().unwrap();
+        chip(
+            &root,
+            "hwmon0",
+            Some("nvme"),
+            &[Input {
+                label: Some("Composite"),
+                ..input(1, "35000")
+            }],
+        );
+        chip(
+            &root,
+            "hwmon1",
+            Some("amdgpu"),
+            &[Input {
+                label: Some("edge"),
+                ..input(1, "50000")
+            }],
+        );
+        chip(
+            &root,
+            "hwmon2",
+            Some("k10temp"),
+            &[Input {
+                label: Some("edge"),
+                ..input(1, "60000")
+            }],
+        );
+
+        let data = read(&root, Some("Composite")).unwrap();
+        assert_eq!(
+            (data.sensor.as_str(), data.celsius),
+            ("nvme: Composite", 35.0)
+        );
+
+        // The first chip with the label, unless the chip is given too
+        let data = read(&root, Some("edge")).unwrap();
+        assert_eq!((data.sensor.as_str(), data.celsius), ("amdgpu: edge", 50.0));
+
+        let data = read(&root, Some("k10temp: edge")).unwrap();
+        assert_eq!(
+            (data.sensor.as_str(), data.celsius),
+            ("k10temp: edge", 60.0)
+        );
+        assert_eq!(data.all.len(), 3);
+    }
+
+    #[test]
+    fn defaults_to_a_cpu_sensor() {
+        let root = TempDir::new().unwrap();
+        chip(&root, "hwmon0", Some("nvme"), &[input(1, "35000")]);
+        chip(&root, "hwmon1", Some("k10temp"), &[input(1, "55000")]);
+
+        let data = read(&root, None).unwrap();
+        assert_eq!(
+            (data.sensor.as_str(), data.celsius),
+            ("k10temp: temp1", 55.0)
+        );
+
+        // Anything will do without a CPU sensor
+        let root = TempDir::new().unwrap();
+        chip(&root, "hwmon0", Some("nvme"), &[input(1, "35000")]);
+
+        assert_eq!(read(&root, None).unwrap().sensor, "nvme: temp1");
+    }
+
+    #[test]
+    fn unknown_sensor() {
+        let root = TempDir::new().unwrap();
+        chip(&root, "hwmon0", Some("nvme"), &[input(1, "35000")]);
+
+        let error = read(&root, Some("Tctl")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No temperature sensor called Tctl, found: nvme: temp1"
+        );
+
+        let empty = TempDir::new().unwrap();
+        assert!(read(&empty, None)
+            .unwrap_err()
+            .to_string()
+            .starts_with("No temperature sensors in"));
+    }
+}
@@ -0,0 +1,674 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use derive_more::derive::Display;
+use iced::{
+    widget::{
+        button, column, container, horizontal_rule, image, mouse_area, row, svg, text, Space,
+    },
+    Alignment, Element, Length, Renderer, Theme,
+};
+use iced_fonts::{nerd, Nerd};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use system_tray::{
+    client::{ActivateRequest, Client, Event},
+    item::{IconPixmap, Status, StatusNotifierItem},
+    menu::{MenuItem, MenuType, ToggleState, ToggleType, TrayMenu},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::util::find_icon;
+
+use super::{Module, ModuleContext, ModuleWidget, ModuleWidgetUpdateOutput};
+
+/// Tells apart the D-Bus names of several tray instances
+static INSTANCES: AtomicUsize = AtomicUsize::new(0);
+
+/// StatusNotifierItem host, keeping track of the tray items through the watcher
+/// and passing clicks on to them
+pub struct Tray {
+    config: TrayConfig,
+    client: Option<(Client, broadcast::Receiver<Event>)>,
+}
+
+impl Tray {
+    fn client(&self) -> miette::Result<&Client> {
+        self.client
+            .as_ref()
+            .map(|(client, _)| client)
+            .ok_or_else(|| miette::miette!("Not connected to the tray watcher"))
+    }
+
+    /// Current items, in a stable order
+    fn items(&self) -> miette::Result<Vec<TrayItem>> {
+        let items = self.client()?.items();
+        let items = items
+            .lock()
+            .map_err(|_| miette::miette!("The tray items got poisoned"))?;
+
+        Ok(items
+            .iter()
+            .filter(|(_, (item, _))| {
+                self.config.show_passive || !matches!(item.status, Status::Passive)
+            })
+            .map(|(address, (item, menu))| TrayItem::new(address, item, menu.as_ref()))
+            .sorted_by(|a, b| (&a.id, &a.address).cmp(&(&b.id, &b.address)))
+            .collect())
+    }
+
+    async fn activate(&self, request: ActivateRequest) -> miette::Result<()> {
+        self.client()?
+            .activate(request)
+            .await
+            .into_diagnostic()
+            .wrap_err("Failed to activate the tray item")
+    }
+
+    fn menu_path(&self, address: &str) -> miette::Result<Option<String>> {
+        let items = self.client()?.items();
+        let items = items
+            .lock()
+            .map_err(|_| miette::miette!("The tray items got poisoned"))?;
+
+        Ok(items.get(address).and_then(|(item, _)| item.menu.clone()))
+    }
+}
+
+impl Module for Tray {
+    type Config = TrayConfig;
+    type Event = TrayEvent;
+    type Output = Vec<TrayItem>;
+
+    type Widget = TrayWidget;
+
+    fn new(config: Self::Config, _context: ModuleContext) -> miette::Result<Self> {
+        Ok(Self {
+            config,
+            client: None,
+        })
+    }
+
+    async fn init(&mut self) -> miette::Result<Self::Output> {
+        let name = format!(
+            "rbar-{}-{}",
+            std::process::id(),
+            INSTANCES.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let client = Client::new(&name)
+            .await
+            .into_diagnostic()
+            .wrap_err("Failed to start the tray host")?;
+        let events = client.subscribe();
+        self.client = Some((client, events));
+
+        self.items()
+    }
+
+    async fn wait(&mut self) -> miette::Result<Self::Event> {
+        let Some((_, events)) = &mut self.client else {
+            return Err(miette::miette!("Not connected to the tray watcher"));
+        };
+
+        // Missed events don't matter, the items get read again anyway
+        match events.recv().await {
+            Ok(_) | Err(RecvError::Lagged(_)) => Ok(TrayEvent::Changed),
+            Err(RecvError::Closed) => Err(miette::miette!("The tray host stopped")),
+        }
+    }
+
+    async fn cycle(&mut self, event: Self::Event) -> miette::Result<Option<Self::Output>> {
+        let request = match event {
+            TrayEvent::Changed => return self.items().map(Some),
+            TrayEvent::Activate(address) => ActivateRequest::Default {
+                address,
+                x: 0,
+                y: 0,
+            },
+            TrayEvent::SecondaryActivate(address) => ActivateRequest::Secondary {
+                address,
+                x: 0,
+                y: 0,
+            },
+            TrayEvent::MenuEntry(address, submenu_id) => match self.menu_path(&address)? {
+                Some(menu_path) => ActivateRequest::MenuItem {
+                    address,
+                    menu_path,
+                    submenu_id,
+                },
+                None => {
+                    tracing::warn!("Tray item {address} has no menu anymore");
+                    return Ok(None);
+                }
+            },
+        };
+
+        // A misbehaving item shouldn't take the whole tray down
+        if let Err(err) = self.activate(request).await {
+            tracing::warn!("{err:?}");
+        }
+
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrayEvent {
+    Changed,
+    Activate(String),
+    SecondaryActivate(String),
+    /// Click on a menu entry of an item, by the item address and the entry id
+    MenuEntry(String, i32),
+}
+
+#[derive(Debug, Clone)]
+pub struct TrayItem {
+    pub id: String,
+    /// D-Bus address of the item, which identifies it
+    pub address: String,
+    pub title: Option<String>,
+    pub icon: Option<TrayIcon>,
+    pub tooltip: Option<String>,
+    /// The item only offers a menu, activating it shows that instead
+    pub item_is_menu: bool,
+    pub needs_attention: bool,
+    pub menu: Vec<TrayMenuEntry>,
+}
+
+impl TrayItem {
+    fn new(address: &str, item: &StatusNotifierItem, menu: Option<&TrayMenu>) -> Self {
+        let tooltip = item
+            .tool_tip
+            .as_ref()
+            .map(|tip| match tip.description.trim() {
+                "" => tip.title.clone(),
+                description => format!("{}\n{description}", tip.title),
+            })
+            .filter(|tip| !tip.trim().is_empty())
+            .or_else(|| item.title.clone());
+
+        let mut entries = vec![];
+        if let Some(menu) = menu {
+            TrayMenuEntry::flatten(&menu.submenus, 0, &mut entries);
+        }
+
+        Self {
+            id: item.id.clone(),
+            address: address.to_string(),
+            title: item.title.clone(),
+            icon: TrayIcon::new(item),
+            tooltip,
+            item_is_menu: item.item_is_menu,
+            needs_attention: matches!(item.status, Status::NeedsAttention),
+            menu: entries,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TrayIcon {
+    Path(PathBuf),
+    Pixmap(image::Handle),
+}
+
+impl TrayIcon {
+    /// Named icons first, looked up in the theme path of the item before the usual locations,
+    /// then the pixmaps the item sends over
+    fn new(item: &StatusNotifierItem) -> Option<Self> {
+        let name = match item.status {
+            Status::NeedsAttention => item
+                .attention_icon_name
+                .as_deref()
+                .or(item.icon_name.as_deref()),
+            _ => item.icon_name.as_deref(),
+        }
+        .filter(|name| !name.is_empty());
+
+        let path = name.and_then(|name| {
+            item.icon_theme_path
+                .as_deref()
+                .filter(|path| !path.is_empty())
+                .and_then(|path| theme_icon(Path::new(path), name))
+                .or_else(|| find_icon(name))
+        });
+
+        path.map(Self::Path)
+            .or_else(|| Self::pixmap(item.icon_pixmap.as_deref()?))
+    }
+
+    /// The largest of the pixmaps, which come as ARGB32 in network byte order
+    fn pixmap(pixmaps: &[IconPixmap]) -> Option<Self> {
+        let pixmap = pixmaps
+            .iter()
+            .filter(|p| p.width > 0 && p.height > 0)
+            .filter(|p| p.pixels.len() == p.width as usize * p.height as usize * 4)
+            .max_by_key(|p| p.width as usize * p.height as usize)?;
+
+        let rgba = pixmap
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|argb| [argb[1], argb[2], argb[3], argb[0]])
+            .collect::<Vec<_>>();
+
+        Some(Self::Pixmap(image::Handle::from_rgba(
+            pixmap.width as u32,
+            pixmap.height as u32,
+            rgba,
+        )))
+    }
+
+    fn view<'a, M: 'a>(&self, size: u16) -> Element<'a, M, Theme, Renderer> {
+        match self {
+            Self::Path(path) if path.extension().is_some_and(|ext| ext == "svg") => {
+                svg(svg::Handle::from_path(path))
+                    .width(size)
+                    .height(size)
+                    .into()
+            }
+            Self::Path(path) => image(image::Handle::from_path(path))
+                .width(size)
+                .height(size)
+                .into(),
+            Self::Pixmap(handle) => image(handle.clone()).width(size).height(size).into(),
+        }
+    }
+}
+
+/// Icon shipped by the application itself, in its own directory
+fn theme_icon(theme: &Path, name: &str) -> Option<PathBuf> {
+    ["svg", "png"]
+        .into_iter()
+        .map(|ext| theme.join(format!("{name}.{ext}")))
+        .find(|path| path.exists())
+}
+
+/// Menu entry, flattened with its depth, submenus being shown inline under their entry
+#[derive(Debug, Clone)]
+pub struct TrayMenuEntry {
+    pub id: i32,
+    pub label: String,
+    pub separator: bool,
+    pub enabled: bool,
+    pub toggle: TrayToggle,
+    pub depth: usize,
+    /// Opens a submenu rather than doing anything on its own
+    pub parent: bool,
+}
+
+impl TrayMenuEntry {
+    fn flatten(items: &[MenuItem], depth: usize, entries: &mut Vec<Self>) {
+        for item in items.iter().filter(|item| item.visible) {
+            let on = matches!(item.toggle_state, ToggleState::On);
+            let toggle = match item.toggle_type {
+                ToggleType::Checkmark => TrayToggle::Check(on),
+                ToggleType::Radio => TrayToggle::Radio(on),
+                _ => TrayToggle::None,
+            };
+
+            entries.push(Self {
+                id: item.id,
+                label: item.label.as_deref().map(mnemonics).unwrap_or_default(),
+                separator: matches!(item.menu_type, MenuType::Separator),
+                enabled: item.enabled,
+                toggle,
+                depth,
+                parent: !item.submenu.is_empty(),
+            });
+
+            Self::flatten(&item.submenu, depth + 1, entries);
+        }
+    }
+}
+
+/// Drops the access key markers from a menu label, `__` being a literal underscore
+fn mnemonics(label: &str) -> String {
+    label
+        .split("__")
+        .map(|part| part.replace('_', ""))
+        .join("_")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayToggle {
+    None,
+    Check(bool),
+    Radio(bool),
+}
+
+#[derive(Debug)]
+pub struct TrayWidget {
+    config: TrayConfig,
+    items: Vec<TrayItem>,
+    /// Address of the item whose menu is open
+    menu: Option<String>,
+    /// Address of the item under the pointer, whose tooltip shows
+    hovered: Option<String>,
+}
+
+impl TrayWidget {
+    fn item(&self, address: &str) -> Option<&TrayItem> {
+        self.items.iter().find(|item| item.address == address)
+    }
+
+    fn has_menu(&self, address: &str) -> bool {
+        self.item(address).is_some_and(|item| !item.menu.is_empty())
+    }
+
+    fn toggle_menu(&mut self, address: String) {
+        self.menu = match self.menu.take() {
+            Some(open) if open == address => None,
+            _ => Some(address),
+        };
+    }
+}
+
+impl ModuleWidget<Tray> for TrayWidget {
+    type Event = TrayWidgetEvent;
+
+    fn new(config: TrayConfig) -> Self {
+        Self {
+            config,
+            items: vec![],
+            menu: None,
+            hovered: None,
+        }
+    }
+
+    fn set(&mut self, items: Vec<TrayItem>) {
+        self.items = items;
+
+        if self
+            .menu
+            .as_deref()
+            .is_some_and(|address| !self.has_menu(address))
+        {
+            self.menu = None;
+        }
+    }
+
+    fn update(&mut self, event: Self::Event) -> Option<ModuleWidgetUpdateOutput<Tray>> {
+        let event = match event {
+            TrayWidgetEvent::Click(address) => {
+                let menu_only = self.item(&address).is_some_and(|item| item.item_is_menu);
+
+                match menu_only && self.has_menu(&address) {
+                    true => {
+                        self.toggle_menu(address);
+                        return None;
+                    }
+                    false => TrayEvent::Activate(address),
+                }
+            }
+            TrayWidgetEvent::MiddleClick(address) => TrayEvent::SecondaryActivate(address),
+            TrayWidgetEvent::RightClick(address) => {
+                if self.has_menu(&address) {
+                    self.toggle_menu(address);
+                }
+
+                return None;
+            }
+            TrayWidgetEvent::MenuEntry(address, id) => {
+                self.menu = None;
+                TrayEvent::MenuEntry(address, id)
+            }
+            TrayWidgetEvent::Hover(address, true) => {
+                self.hovered = Some(address);
+                return None;
+            }
+            TrayWidgetEvent::Hover(address, false) => {
+                // Leaving one item can come after entering the next one
+                if self.hovered.as_ref() == Some(&address) {
+                    self.hovered = None;
+                }
+
+                return None;
+            }
+        };
+
+        Some(ModuleWidgetUpdateOutput::module(event))
+    }
+
+    fn view(&self) -> Element<'_, Self::Event, Theme, Renderer> {
+        let size = self.config.icon_size;
+
+        let items = self.items.iter().map(|item| {
+            let icon = match &item.icon {
+                Some(icon) => icon.view(size),
+                None => text(nerd::icon_to_string(Nerd::Application))
+                    .font(iced_fonts::NERD_FONT)
+                    .into(),
+            };
+
+            let icon = match item.needs_attention {
+                true => container(icon).style(container::bordered_box).into(),
+                false => icon,
+            };
+
+            mouse_area(icon)
+                .on_press(TrayWidgetEvent::Click(item.address.clone()))
+                .on_middle_press(TrayWidgetEvent::MiddleClick(item.address.clone()))
+                .on_right_press(TrayWidgetEvent::RightClick(item.address.clone()))
+                .on_enter(TrayWidgetEvent::Hover(item.address.clone(), true))
+                .on_exit(TrayWidgetEvent::Hover(item.address.clone(), false))
+                .into()
+        });
+
+        row(items).spacing(8).align_y(Alignment::Center).into()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        self.item(self.hovered.as_deref()?)?.tooltip.clone()
+    }
+
+    fn has_popup(&self) -> bool {
+        self.menu.is_some()
+    }
+
+    fn popup(&self) -> Option<Element<'_, Self::Event, Theme, Renderer>> {
+        let item = self.item(self.menu.as_deref()?)?;
+
+        let entries = item.menu.iter().map(|entry| {
+            if entry.separator {
+                return horizontal_rule(1).into();
+            }
+
+            let toggle = match entry.toggle {
+                TrayToggle::None => "",
+                TrayToggle::Check(true) => "✓",
+                TrayToggle::Radio(true) => "●",
+                TrayToggle::Check(false) | TrayToggle::Radio(false) => " ",
+            };
+
+            let label = row![Space::with_width(entry.depth as f32 * 12.0)]
+                .push_maybe((!toggle.is_empty()).then(|| text(toggle).width(12)))
+                .push(text(&entry.label))
+                .spacing(5)
+                .align_y(Alignment::Center);
+
+            let press = (entry.enabled && !entry.parent)
+                .then(|| TrayWidgetEvent::MenuEntry(item.address.clone(), entry.id));
+
+            button(label)
+                .style(button::text)
+                .width(Length::Fill)
+                .on_press_maybe(press)
+                .into()
+        });
+
+        Some(
+            container(column(entries).spacing(2))
+                .padding(10)
+                .style(container::rounded_box)
+                .into(),
+        )
+    }
+
+    fn close_popup(&mut self) {
+        self.menu = None;
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::json!({
+            "items": self
+                .items
+                .iter()
+                .map(|item| serde_json::json!({
+                    "id": item.id,
+                    "address": item.address,
+                    "title": item.title,
+                    "needs_attention": item.needs_attention,
+                }))
+                .collect::<Vec<_>>(),
+            "menu_open": self.menu,
+        })
+    }
+}
+
+/// Input on a tray item, by its address
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum TrayWidgetEvent {
+    Click(String),
+    MiddleClick(String),
+    RightClick(String),
+    /// Click on an entry of the open menu, by its id
+    MenuEntry(String, i32),
+    /// Pointer entering (`true`) or leaving an item
+    Hover(String, bool),
+}
+
+#[derive(SmartDefault, Display, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[display("Tray")]
+#[serde(default)]
+pub struct TrayConfig {
+    /// Size of the item icons, in pixels
+    #[default = 16]
+    pub icon_size: u16,
+    /// Shows the items that don't ask for attention at the moment as well
+    pub show_passive: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixmap(width: i32, height: i32, pixels: Vec<u8>) -> IconPixmap {
+        IconPixmap {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn menu_item(id: i32, label: &str, submenu: Vec<MenuItem>) -> MenuItem {
+        MenuItem {
+            id,
+            label: Some(label.into()),
+            enabled: true,
+            visible: true,
+            submenu,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pixmap_argb_to_rgba() {
+        let icon = TrayIcon::pixmap(&[pixmap(1, 1, vec![0xff, 0x10, 0x20, 0x30])]);
+
+        let (width, height, pixels) = match icon {
+            Some(TrayIcon::Pixmap(image::Handle::Rgba {
+                width,
+                height,
+                pixels,
+                ..
+            })) => (width, height, pixels),
+            icon => panic!("expected an RGBA pixmap, got {icon:?}"),
+        };
+
+        assert_eq!((width, height), (1, 1));
+        assert_eq!(&pixels[..], [0x10, 0x20, 0x30, 0xff]);
+    }
+
+    #[test]
+    fn pixmap_picks_the_largest() {
+        let icon = TrayIcon::pixmap(&[
+            pixmap(1, 1, vec![0; 4]),
+            pixmap(2, 2, vec![0; 16]),
+            // Sizes that don't match the pixels get skipped
+            pixmap(4, 4, vec![0; 4]),
+            pixmap(0, 0, vec![]),
+        ]);
+
+        assert!(matches!(
+            icon,
+            Some(TrayIcon::Pixmap(image::Handle::Rgba {
+                width: 2,
+                height: 2,
+                ..
+            }))
+        ));
+        assert!(TrayIcon::pixmap(&[pixmap(0, 0, vec![])]).is_none());
+    }
+
+    #[test]
+    fn mnemonics_removed() {
+        assert_eq!(mnemonics("_Open"), "Open");
+        assert_eq!(mnemonics("Save _As"), "Save As");
+        assert_eq!(mnemonics("snake__case"), "snake_case");
+        assert_eq!(mnemonics("_Copy __path__"), "Copy _path_");
+    }
+
+    #[test]
+    fn flatten_menu() {
+        let mut hidden = menu_item(2, "Hidden", vec![menu_item(3, "Inside hidden", vec![])]);
+        hidden.visible = false;
+
+        let mut check = menu_item(5, "_Mute", vec![]);
+        check.toggle_type = ToggleType::Checkmark;
+        check.toggle_state = ToggleState::On;
+
+        let mut radio = menu_item(6, "Radio", vec![]);
+        radio.toggle_type = ToggleType::Radio;
+
+        let mut separator = menu_item(7, "", vec![]);
+        separator.menu_type = MenuType::Separator;
+
+        let mut disabled = menu_item(8, "Disabled", vec![]);
+        disabled.enabled = false;
+
+        let items = [
+            menu_item(1, "Open", vec![]),
+            hidden,
+            menu_item(4, "Options", vec![check, radio]),
+            separator,
+            disabled,
+        ];
+
+        let mut entries = vec![];
+        TrayMenuEntry::flatten(&items, 0, &mut entries);
+
+        let summary = entries
+            .iter()
+            .map(|e| (e.id, e.label.as_str(), e.depth, e.toggle, e.parent))
+            .collect_vec();
+        assert_eq!(
+            summary,
+            [
+                (1, "Open", 0, TrayToggle::None, false),
+                (4, "Options", 0, TrayToggle::None, true),
+                (5, "Mute", 1, TrayToggle::Check(true), false),
+                (6, "Radio", 1, TrayToggle::Radio(false), false),
+                (7, "", 0, TrayToggle::None, false),
+                (8, "Disabled", 0, TrayToggle::None, false),
+            ]
+        );
+
+        assert!(entries[4].separator);
+        assert!(entries.iter().filter(|e| e.id != 7).all(|e| !e.separator));
+        assert!(!entries[5].enabled);
+    }
+}
@@ -1,30 +1,90 @@
-use std::sync::Arc;
-
-pub trait ResultExt<T, E> {
-    fn tokio_mutex(self) -> Result<tokio::sync::Mutex<T>, E>;
-    fn arc(self) -> Result<Arc<T>, E>;
-    fn some(self) -> Result<Option<T>, E>;
-    fn err_str(self) -> Result<T, String>
-    where
-        E: ToString;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+/// Looks up an application icon by its freedesktop name in the usual icon locations,
+/// without going through the whole icon theme spec
+pub fn find_icon(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    if path.is_absolute() {
+        return path.exists().then(|| path.to_path_buf());
+    }
+
+    let data_dirs =
+        std::env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".into());
+    let data_dirs = directories::BaseDirs::new()
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .into_iter()
+        .chain(std::env::split_paths(&data_dirs));
+
+    let candidates = data_dirs.flat_map(|dir| {
+        let hicolor = dir.join("icons/hicolor");
+        ["scalable", "48x48", "32x32", "24x24", "64x64", "128x128"]
+            .into_iter()
+            .map(move |size| hicolor.join(size).join("apps"))
+            .chain([dir.join("pixmaps")])
+    });
+
+    candidates
+        .flat_map(|dir| ["svg", "png"].map(|ext| dir.join(format!("{name}.{ext}"))))
+        .find(|path| path.exists())
 }
 
-impl<T, E> ResultExt<T, E> for Result<T, E> {
-    fn tokio_mutex(self) -> Result<tokio::sync::Mutex<T>, E> {
-        self.map(tokio::sync::Mutex::new)
+/// Size in binary units, e.g. `3.2GiB`
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
     }
-    fn arc(self) -> Result<Arc<T>, E> {
-        self.map(Arc::new)
+
+    match unit {
+        0 => format!("{bytes}B"),
+        _ => format!("{size:.1}{}", UNITS[unit]),
     }
+}
 
-    fn some(self) -> Result<Option<T>, E> {
-        self.map(Some)
+/// Share of the total that's used, in percent, nothing being used of nothing
+pub fn percent(used: u64, total: u64) -> f32 {
+    match total {
+        0 => 0.0,
+        total => used as f32 * 100.0 / total as f32,
     }
+}
+
+/// `sh -c command`, for the commands users put in the config
+pub fn shell_command(command: &str) -> tokio::process::Command {
+    let mut cmd = tokio::process::Command::new("sh");
+    cmd.arg("-c").arg(command).stdin(Stdio::null());
+    cmd
+}
+
+/// Starts a user command in the background, logging how it went instead of waiting for it.
+/// Doesn't need the async runtime, so the UI can run commands too
+pub fn spawn_command(command: &str) {
+    let child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
 
-    fn err_str(self) -> Result<T, String>
-    where
-        E: ToString,
-    {
-        self.map_err(|e| e.to_string())
+    match child {
+        Ok(mut child) => {
+            let command = command.to_string();
+            std::thread::spawn(move || match child.wait() {
+                Ok(status) if !status.success() => {
+                    tracing::warn!("`{command}` exited with {status}")
+                }
+                Ok(_) => {}
+                Err(err) => tracing::warn!("Failed to wait for `{command}`: {err}"),
+            });
+        }
+        Err(err) => tracing::error!("Failed to run `{command}`: {err}"),
     }
 }